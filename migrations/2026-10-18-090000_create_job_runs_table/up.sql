@@ -0,0 +1,13 @@
+CREATE TABLE job_runs (
+    id SERIAL PRIMARY KEY,
+    job_name TEXT NOT NULL,
+    "trigger" SMALLINT NOT NULL,
+    status SMALLINT NOT NULL,
+    processed_count BIGINT NOT NULL DEFAULT 0,
+    affected_count BIGINT NOT NULL DEFAULT 0,
+    error TEXT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX job_runs_job_name_started_at_idx ON job_runs (job_name, started_at DESC);
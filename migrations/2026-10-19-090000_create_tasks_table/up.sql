@@ -0,0 +1,17 @@
+CREATE TABLE tasks (
+    id SERIAL PRIMARY KEY,
+    kind SMALLINT NOT NULL,
+    status SMALLINT NOT NULL DEFAULT 0,
+    payload JSONB NOT NULL,
+    result JSONB NULL,
+    error TEXT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 3,
+    run_after TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_by TEXT NULL,
+    locked_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX tasks_status_run_after_idx ON tasks (status, run_after);
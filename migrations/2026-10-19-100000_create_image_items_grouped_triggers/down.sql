@@ -0,0 +1,3 @@
+DROP TRIGGER image_items_grouped_update ON image_items;
+DROP TRIGGER image_items_grouped_insert ON image_items;
+DROP FUNCTION sync_image_items_grouped;
\ No newline at end of file
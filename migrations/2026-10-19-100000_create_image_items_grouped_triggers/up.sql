@@ -0,0 +1,22 @@
+CREATE FUNCTION sync_image_items_grouped() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO image_items_grouped (image_item_id, "date")
+    VALUES (NEW.id, NEW."date")
+    ON CONFLICT (image_item_id) DO UPDATE SET "date" = EXCLUDED."date";
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER image_items_grouped_insert
+AFTER INSERT ON image_items
+FOR EACH ROW EXECUTE FUNCTION sync_image_items_grouped();
+
+CREATE TRIGGER image_items_grouped_update
+AFTER UPDATE OF "date" ON image_items
+FOR EACH ROW
+WHEN (OLD."date" IS DISTINCT FROM NEW."date")
+EXECUTE FUNCTION sync_image_items_grouped();
+
+INSERT INTO image_items_grouped (image_item_id, "date")
+SELECT id, "date" FROM image_items
+ON CONFLICT (image_item_id) DO UPDATE SET "date" = EXCLUDED."date";
@@ -0,0 +1 @@
+ALTER TABLE site_storage DROP COLUMN metadata;
\ No newline at end of file
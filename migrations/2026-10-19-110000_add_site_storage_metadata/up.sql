@@ -0,0 +1 @@
+ALTER TABLE site_storage ADD COLUMN metadata JSONB NULL;
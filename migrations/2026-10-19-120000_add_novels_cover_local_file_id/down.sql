@@ -0,0 +1 @@
+ALTER TABLE novels DROP COLUMN cover_local_file_id;
\ No newline at end of file
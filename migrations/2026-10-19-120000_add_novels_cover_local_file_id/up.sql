@@ -0,0 +1 @@
+ALTER TABLE novels ADD COLUMN cover_local_file_id TEXT NULL REFERENCES local_files(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+DROP TABLE novel_chapters;
\ No newline at end of file
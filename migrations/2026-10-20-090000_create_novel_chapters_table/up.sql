@@ -0,0 +1,12 @@
+CREATE TABLE novel_chapters (
+    id SERIAL PRIMARY KEY,
+    novel_id INTEGER NOT NULL REFERENCES novels(id) ON DELETE CASCADE,
+    "position" INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    word_count INTEGER NOT NULL DEFAULT 0,
+    published_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT novel_chapters_novel_id_position_key UNIQUE (novel_id, "position") DEFERRABLE INITIALLY DEFERRED
+);
@@ -0,0 +1,2 @@
+DROP TABLE series_novels;
+DROP TABLE series;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE series (
+    id SERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    description TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE series_novels (
+    id SERIAL PRIMARY KEY,
+    series_id INTEGER NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+    novel_id INTEGER NOT NULL UNIQUE REFERENCES novels(id) ON DELETE CASCADE,
+    "position" INTEGER NOT NULL,
+    CONSTRAINT series_novels_series_id_position_key UNIQUE (series_id, "position") DEFERRABLE INITIALLY DEFERRED
+);
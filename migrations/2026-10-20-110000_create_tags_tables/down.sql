@@ -0,0 +1,6 @@
+DROP TRIGGER tags_novel_count_delete ON novels;
+DROP TRIGGER tags_novel_count_update ON novels;
+DROP TRIGGER tags_novel_count_insert ON novels;
+DROP FUNCTION sync_tags_novel_count();
+DROP TABLE tag_implications;
+DROP TABLE tags;
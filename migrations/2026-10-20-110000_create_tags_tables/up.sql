@@ -0,0 +1,84 @@
+CREATE TABLE tags (
+    id SERIAL PRIMARY KEY,
+    -- 0: 通用, 1: genre, 2: character, 3: language
+    namespace SMALLINT NOT NULL DEFAULT 0,
+    name TEXT NOT NULL,
+    -- 写入novels.tags的字符串
+    key TEXT GENERATED ALWAYS AS (
+        CASE namespace
+            WHEN 1 THEN 'genre:' || name
+            WHEN 2 THEN 'character:' || name
+            WHEN 3 THEN 'language:' || name
+            ELSE name
+        END
+    ) STORED UNIQUE,
+    -- 不为空时该标签为别名，写入时会被替换为alias_of指向的标签
+    alias_of INTEGER NULL REFERENCES tags(id) ON DELETE CASCADE,
+    novel_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT tags_namespace_name_key UNIQUE (namespace, name),
+    CONSTRAINT tags_alias_of_check CHECK (alias_of <> id)
+);
+
+CREATE TABLE tag_implications (
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    implied_tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (tag_id, implied_tag_id),
+    CONSTRAINT tag_implications_check CHECK (tag_id <> implied_tag_id)
+);
+
+CREATE FUNCTION sync_tags_novel_count() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE tags SET novel_count = novel_count - 1 WHERE key = ANY(OLD.tags);
+        RETURN OLD;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        UPDATE tags SET novel_count = novel_count - 1
+        WHERE key = ANY(OLD.tags) AND key <> ALL(array_remove(NEW.tags, NULL));
+        UPDATE tags SET novel_count = novel_count + 1
+        WHERE key = ANY(NEW.tags) AND key <> ALL(array_remove(OLD.tags, NULL));
+    ELSE
+        UPDATE tags SET novel_count = novel_count + 1 WHERE key = ANY(NEW.tags);
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tags_novel_count_insert
+AFTER INSERT ON novels
+FOR EACH ROW EXECUTE FUNCTION sync_tags_novel_count();
+
+CREATE TRIGGER tags_novel_count_update
+AFTER UPDATE OF tags ON novels
+FOR EACH ROW
+WHEN (OLD.tags IS DISTINCT FROM NEW.tags)
+EXECUTE FUNCTION sync_tags_novel_count();
+
+CREATE TRIGGER tags_novel_count_delete
+AFTER DELETE ON novels
+FOR EACH ROW EXECUTE FUNCTION sync_tags_novel_count();
+
+-- 导入已有的标签，带有已知前缀的归入对应的namespace
+INSERT INTO tags (namespace, name)
+SELECT DISTINCT
+    CASE split_part(tag, ':', 1)
+        WHEN 'genre' THEN 1
+        WHEN 'character' THEN 2
+        WHEN 'language' THEN 3
+        ELSE 0
+    END,
+    CASE
+        WHEN split_part(tag, ':', 1) IN ('genre', 'character', 'language')
+            THEN substr(tag, strpos(tag, ':') + 1)
+        ELSE tag
+    END
+FROM (SELECT DISTINCT UNNEST(tags) AS tag FROM novels) t
+WHERE tag IS NOT NULL AND tag <> ''
+ON CONFLICT DO NOTHING;
+
+UPDATE tags SET novel_count = c.count
+FROM (SELECT tag, COUNT(*) AS count FROM (SELECT DISTINCT id, UNNEST(tags) AS tag FROM novels) n GROUP BY tag) c
+WHERE tags.key = c.tag;
@@ -0,0 +1,7 @@
+DROP TRIGGER tags_image_count_delete ON image_items;
+DROP TRIGGER tags_image_count_update ON image_items;
+DROP TRIGGER tags_image_count_insert ON image_items;
+DROP FUNCTION sync_tags_image_count();
+DROP INDEX image_items_tags_idx;
+ALTER TABLE tags DROP COLUMN image_count;
+ALTER TABLE image_items DROP COLUMN tags;
@@ -0,0 +1,39 @@
+ALTER TABLE image_items ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE tags ADD COLUMN image_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX image_items_tags_idx ON image_items USING GIN (tags);
+
+CREATE FUNCTION sync_tags_image_count() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE tags SET image_count = image_count - 1 WHERE key = ANY(OLD.tags);
+        RETURN OLD;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        UPDATE tags SET image_count = image_count - 1
+        WHERE key = ANY(OLD.tags) AND key <> ALL(array_remove(NEW.tags, NULL));
+        UPDATE tags SET image_count = image_count + 1
+        WHERE key = ANY(NEW.tags) AND key <> ALL(array_remove(OLD.tags, NULL));
+    ELSE
+        UPDATE tags SET image_count = image_count + 1 WHERE key = ANY(NEW.tags);
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tags_image_count_insert
+AFTER INSERT ON image_items
+FOR EACH ROW EXECUTE FUNCTION sync_tags_image_count();
+
+CREATE TRIGGER tags_image_count_update
+AFTER UPDATE OF tags ON image_items
+FOR EACH ROW
+WHEN (OLD.tags IS DISTINCT FROM NEW.tags)
+EXECUTE FUNCTION sync_tags_image_count();
+
+CREATE TRIGGER tags_image_count_delete
+AFTER DELETE ON image_items
+FOR EACH ROW EXECUTE FUNCTION sync_tags_image_count();
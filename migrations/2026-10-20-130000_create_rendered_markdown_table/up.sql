@@ -0,0 +1,6 @@
+CREATE TABLE rendered_markdown (
+    -- 渲染版本和原文的hash
+    hash TEXT PRIMARY KEY,
+    html TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+DROP INDEX novels_author_id_idx;
+ALTER TABLE novels DROP COLUMN author_id;
@@ -0,0 +1,13 @@
+ALTER TABLE novels ADD COLUMN author_id INTEGER NULL REFERENCES authors(id) ON DELETE SET NULL;
+
+CREATE INDEX novels_author_id_idx ON novels (author_id);
+
+-- 只关联名称唯一匹配的作者，忽略大小写和首尾空白
+UPDATE novels SET author_id = matched.author_id
+FROM (
+    SELECT lower(trim(name)) AS name, MIN(id) AS author_id
+    FROM authors
+    GROUP BY lower(trim(name))
+    HAVING COUNT(*) = 1
+) matched
+WHERE lower(trim(novels.author_name)) = matched.name;
@@ -0,0 +1,9 @@
+CREATE TABLE author_aliases (
+    id SERIAL PRIMARY KEY,
+    author_id INTEGER NOT NULL REFERENCES authors(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT author_aliases_author_id_name_key UNIQUE (author_id, name)
+);
+
+CREATE INDEX author_aliases_name_idx ON author_aliases (name);
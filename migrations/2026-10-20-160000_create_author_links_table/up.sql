@@ -0,0 +1,13 @@
+CREATE TABLE author_links (
+    id SERIAL PRIMARY KEY,
+    author_id INTEGER NOT NULL REFERENCES authors(id) ON DELETE CASCADE,
+    -- 0: 个人网站, 1: pixiv, 2: twitter, 3: fanbox, 4: skeb, 5: danbooru
+    platform SMALLINT NOT NULL,
+    -- 平台上的账号，个人网站为去掉协议的地址
+    handle TEXT NOT NULL,
+    url TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT author_links_platform_handle_key UNIQUE (platform, handle)
+);
+
+CREATE INDEX author_links_author_id_idx ON author_links (author_id);
@@ -0,0 +1,2 @@
+ALTER TABLE authors DROP COLUMN banner_local_file_id;
+ALTER TABLE authors DROP COLUMN avatar_local_file_id;
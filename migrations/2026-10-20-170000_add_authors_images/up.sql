@@ -0,0 +1,2 @@
+ALTER TABLE authors ADD COLUMN avatar_local_file_id TEXT NULL REFERENCES local_files(id) ON DELETE SET NULL;
+ALTER TABLE authors ADD COLUMN banner_local_file_id TEXT NULL REFERENCES local_files(id) ON DELETE SET NULL;
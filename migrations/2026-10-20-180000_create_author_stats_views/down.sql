@@ -0,0 +1,3 @@
+DROP MATERIALIZED VIEW author_monthly_stats;
+DROP VIEW author_monthly_stats_live;
+DROP INDEX image_items_author_id_idx;
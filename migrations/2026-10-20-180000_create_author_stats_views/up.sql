@@ -0,0 +1,43 @@
+CREATE INDEX image_items_author_id_idx ON image_items (author_id);
+
+-- 按月统计作者的作品数量，作者筛选可以下推到各个子查询
+CREATE VIEW author_monthly_stats_live AS
+SELECT
+    author_id,
+    month,
+    SUM(image_items)::BIGINT AS image_items,
+    SUM(files)::BIGINT AS files,
+    SUM(nsfw_image_items)::BIGINT AS nsfw_image_items,
+    SUM(novels)::BIGINT AS novels,
+    SUM(nsfw_novels)::BIGINT AS nsfw_novels
+FROM (
+    SELECT
+        i.author_id,
+        date_trunc('month', i.date::TIMESTAMP)::DATE AS month,
+        1 AS image_items,
+        (SELECT COUNT(*) FROM image_items_local_files f WHERE f.image_item_id = i.id) AS files,
+        i.nsfw::INTEGER AS nsfw_image_items,
+        0 AS novels,
+        0 AS nsfw_novels
+    FROM image_items i
+    WHERE i.author_id IS NOT NULL
+    UNION ALL
+    SELECT
+        n.author_id,
+        date_trunc('month', n.created_at AT TIME ZONE 'UTC')::DATE AS month,
+        0,
+        0,
+        0,
+        1,
+        n.nsfw::INTEGER
+    FROM novels n
+    WHERE n.author_id IS NOT NULL
+) works
+GROUP BY author_id, month;
+
+-- 由定时任务刷新
+CREATE MATERIALIZED VIEW author_monthly_stats AS
+SELECT * FROM author_monthly_stats_live;
+
+-- REFRESH ... CONCURRENTLY需要唯一索引
+CREATE UNIQUE INDEX author_monthly_stats_author_id_month_idx ON author_monthly_stats (author_id, month);
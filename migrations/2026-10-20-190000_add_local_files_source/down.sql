@@ -0,0 +1,8 @@
+DROP INDEX local_files_source_final_url_idx;
+DROP INDEX local_files_source_url_idx;
+
+ALTER TABLE local_files DROP COLUMN importer;
+ALTER TABLE local_files DROP COLUMN source_headers;
+ALTER TABLE local_files DROP COLUMN fetched_at;
+ALTER TABLE local_files DROP COLUMN source_final_url;
+ALTER TABLE local_files DROP COLUMN source_url;
@@ -0,0 +1,8 @@
+ALTER TABLE local_files ADD COLUMN source_url TEXT NULL;
+ALTER TABLE local_files ADD COLUMN source_final_url TEXT NULL;
+ALTER TABLE local_files ADD COLUMN fetched_at TIMESTAMPTZ NULL;
+ALTER TABLE local_files ADD COLUMN source_headers JSONB NULL;
+ALTER TABLE local_files ADD COLUMN importer TEXT NULL;
+
+CREATE INDEX local_files_source_url_idx ON local_files (source_url);
+CREATE INDEX local_files_source_final_url_idx ON local_files (source_final_url);
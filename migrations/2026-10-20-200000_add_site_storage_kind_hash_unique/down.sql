@@ -0,0 +1,3 @@
+DROP INDEX site_storage_kind_hash_key;
+
+DROP TABLE orphaned_objects;
@@ -0,0 +1,38 @@
+-- 同一类型、相同内容的文件只保留最早写入的一条，引用改为指向保留的记录
+UPDATE novels
+SET object_id = kept.id
+FROM site_storage dup
+JOIN LATERAL (
+    SELECT MIN(id) AS id FROM site_storage s WHERE s.kind = dup.kind AND s.hash = dup.hash
+) kept ON TRUE
+WHERE novels.object_id = dup.id
+  AND dup.id <> kept.id;
+
+-- 待删除的S3对象，由purge_orphaned_objects任务清理
+CREATE TABLE orphaned_objects (
+    key TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- 重复记录的对象在保留的记录没有使用同一个key时才需要删除
+INSERT INTO orphaned_objects (key)
+SELECT DISTINCT dup.key
+FROM site_storage dup
+WHERE EXISTS (
+    SELECT 1 FROM site_storage s WHERE s.kind = dup.kind AND s.hash = dup.hash AND s.id < dup.id
+)
+AND NOT EXISTS (
+    SELECT 1 FROM site_storage kept
+    WHERE kept.key = dup.key
+      AND NOT EXISTS (
+          SELECT 1 FROM site_storage s WHERE s.kind = kept.kind AND s.hash = kept.hash AND s.id < kept.id
+      )
+);
+
+DELETE FROM site_storage a
+USING site_storage b
+WHERE a.kind = b.kind
+  AND a.hash = b.hash
+  AND a.id > b.id;
+
+CREATE UNIQUE INDEX site_storage_kind_hash_key ON site_storage (kind, hash);
@@ -0,0 +1,4 @@
+DROP MATERIALIZED VIEW author_tag_stats;
+DROP MATERIALIZED VIEW author_appearance;
+DROP VIEW author_tag_stats_live;
+DROP VIEW author_appearance_live;
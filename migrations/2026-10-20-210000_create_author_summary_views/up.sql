@@ -0,0 +1,37 @@
+-- 作者作品的首次和最近出现日期
+CREATE VIEW author_appearance_live AS
+SELECT
+    author_id,
+    MIN(date) AS first_appearance,
+    MAX(date) AS last_appearance
+FROM (
+    SELECT author_id, date FROM image_items WHERE author_id IS NOT NULL
+    UNION ALL
+    SELECT author_id, (created_at AT TIME ZONE 'UTC')::DATE FROM novels WHERE author_id IS NOT NULL
+) works
+GROUP BY author_id;
+
+-- 作者作品中各个标签的使用次数
+CREATE VIEW author_tag_stats_live AS
+SELECT
+    author_id,
+    tag,
+    COUNT(*)::BIGINT AS count
+FROM (
+    SELECT author_id, UNNEST(tags) AS tag FROM image_items WHERE author_id IS NOT NULL
+    UNION ALL
+    SELECT author_id, UNNEST(tags) AS tag FROM novels WHERE author_id IS NOT NULL
+) works
+WHERE tag IS NOT NULL
+GROUP BY author_id, tag;
+
+-- 与author_monthly_stats一起由定时任务刷新
+CREATE MATERIALIZED VIEW author_appearance AS
+SELECT * FROM author_appearance_live;
+
+CREATE UNIQUE INDEX author_appearance_author_id_idx ON author_appearance (author_id);
+
+CREATE MATERIALIZED VIEW author_tag_stats AS
+SELECT * FROM author_tag_stats_live;
+
+CREATE UNIQUE INDEX author_tag_stats_author_id_tag_idx ON author_tag_stats (author_id, tag);
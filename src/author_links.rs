@@ -0,0 +1,579 @@
+use diesel::{
+    delete, insert_into, result::DatabaseErrorKind, ExpressionMethods, OptionalExtension, QueryDsl,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use log::warn;
+use reqwest::Url;
+
+use crate::{
+    misc::enums::AuthorPlatform,
+    models::{Author, AuthorLink},
+    schema,
+};
+
+/// 从作者链接中识别平台账号
+pub trait LinkRecognizer: Sync {
+    fn platform(&self) -> i16;
+
+    /// 返回地址对应的账号，不属于该平台时返回None
+    fn recognize(&self, url: &Url) -> Option<String>;
+}
+
+/// 按顺序尝试，个人网站可以匹配任意地址，必须放在最后
+const RECOGNIZERS: &[&dyn LinkRecognizer] = &[
+    &PixivRecognizer,
+    &TwitterRecognizer,
+    &FanboxRecognizer,
+    &SkebRecognizer,
+    &DanbooruRecognizer,
+    &WebsiteRecognizer,
+];
+
+/// 解析后的作者链接
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedLink {
+    pub platform: i16,
+    pub handle: String,
+    pub url: String,
+}
+
+fn host(url: &Url) -> Option<String> {
+    url.host_str()
+        .map(|v| v.trim_start_matches("www.").to_ascii_lowercase())
+}
+
+fn path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|v| v.filter(|v| !v.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn is_handle(value: &str, max_len: usize) -> bool {
+    !value.is_empty()
+        && value.len() <= max_len
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+struct PixivRecognizer;
+
+impl LinkRecognizer for PixivRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Pixiv as i16
+    }
+
+    /// `pixiv.net/users/<id>`、`pixiv.net/en/users/<id>`和旧的`member.php?id=<id>`
+    fn recognize(&self, url: &Url) -> Option<String> {
+        if host(url)? != "pixiv.net" {
+            return None;
+        }
+
+        let segments = path_segments(url);
+        let id = match segments.as_slice() {
+            ["users", id, ..] | [_, "users", id, ..] => id.to_string(),
+            ["member.php"] | ["member_illust.php"] => url
+                .query_pairs()
+                .find(|(k, _)| k == "id")
+                .map(|(_, v)| v.into_owned())?,
+            _ => return None,
+        };
+
+        (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then_some(id)
+    }
+}
+
+struct TwitterRecognizer;
+
+/// 不是用户名的一级路径
+const TWITTER_RESERVED: &[&str] = &[
+    "home",
+    "i",
+    "intent",
+    "search",
+    "hashtag",
+    "share",
+    "explore",
+    "settings",
+    "messages",
+    "notifications",
+];
+
+impl LinkRecognizer for TwitterRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Twitter as i16
+    }
+
+    /// `twitter.com/<handle>`或`x.com/<handle>`，用户名不区分大小写
+    fn recognize(&self, url: &Url) -> Option<String> {
+        let host = host(url)?;
+        if !matches!(
+            host.as_str(),
+            "twitter.com" | "mobile.twitter.com" | "x.com" | "mobile.x.com"
+        ) {
+            return None;
+        }
+
+        let handle = path_segments(url)
+            .into_iter()
+            .next()?
+            .trim_start_matches('@')
+            .to_owned();
+
+        (is_handle(&handle, 15)
+            && !handle.contains('-')
+            && !TWITTER_RESERVED.contains(&handle.to_ascii_lowercase().as_str()))
+        .then(|| handle.to_ascii_lowercase())
+    }
+}
+
+struct FanboxRecognizer;
+
+impl LinkRecognizer for FanboxRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Fanbox as i16
+    }
+
+    /// `<handle>.fanbox.cc`或`fanbox.cc/@<handle>`
+    fn recognize(&self, url: &Url) -> Option<String> {
+        let host = host(url)?;
+
+        let handle = if host == "fanbox.cc" {
+            path_segments(url)
+                .into_iter()
+                .next()?
+                .strip_prefix('@')?
+                .to_owned()
+        } else {
+            let handle = host.strip_suffix(".fanbox.cc")?;
+            if handle == "api" || handle.contains('.') {
+                return None;
+            }
+            handle.to_owned()
+        };
+
+        is_handle(&handle, 64).then(|| handle.to_ascii_lowercase())
+    }
+}
+
+struct SkebRecognizer;
+
+impl LinkRecognizer for SkebRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Skeb as i16
+    }
+
+    /// `skeb.jp/@<handle>`
+    fn recognize(&self, url: &Url) -> Option<String> {
+        if host(url)? != "skeb.jp" {
+            return None;
+        }
+
+        let handle = path_segments(url)
+            .into_iter()
+            .next()?
+            .strip_prefix('@')?
+            .to_owned();
+
+        is_handle(&handle, 64).then(|| handle.to_ascii_lowercase())
+    }
+}
+
+struct DanbooruRecognizer;
+
+impl LinkRecognizer for DanbooruRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Danbooru as i16
+    }
+
+    /// `danbooru.donmai.us/posts?tags=<画师标签>`
+    fn recognize(&self, url: &Url) -> Option<String> {
+        if host(url)? != "danbooru.donmai.us" || path_segments(url) != ["posts"] {
+            return None;
+        }
+
+        let tag = url
+            .query_pairs()
+            .find(|(k, _)| k == "tags")
+            .map(|(_, v)| v.trim().to_ascii_lowercase())?;
+
+        (!tag.is_empty() && !tag.contains(char::is_whitespace)).then_some(tag)
+    }
+}
+
+struct WebsiteRecognizer;
+
+/// 不影响页面内容的查询参数，`utm_`开头的参数也会被去掉
+const WEBSITE_TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "ref", "ref_src", "si"];
+
+impl LinkRecognizer for WebsiteRecognizer {
+    fn platform(&self) -> i16 {
+        AuthorPlatform::Website as i16
+    }
+
+    /// 去掉协议、`www.`、末尾`/`和跟踪参数的地址，其余查询参数保留
+    fn recognize(&self, url: &Url) -> Option<String> {
+        let host = host(url)?;
+        let path = url.path().trim_end_matches('/');
+
+        let pairs = url
+            .query_pairs()
+            .filter(|(k, _)| {
+                !k.starts_with("utm_") && !WEBSITE_TRACKING_PARAMS.contains(&k.as_ref())
+            })
+            .collect::<Vec<_>>();
+
+        if pairs.is_empty() {
+            return Some(format!("{}{}", host, path));
+        }
+
+        let mut query = url.to_owned();
+        query.set_query(None);
+        query.query_pairs_mut().extend_pairs(pairs);
+
+        Some(format!(
+            "{}{}?{}",
+            host,
+            path,
+            query.query().unwrap_or_default()
+        ))
+    }
+}
+
+/// 识别作者链接，只接受http和https地址
+pub fn parse_link(url: &str) -> Option<ParsedLink> {
+    let url = url.trim();
+    let parsed = Url::parse(url).ok()?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+
+    RECOGNIZERS.iter().find_map(|recognizer| {
+        recognizer.recognize(&parsed).map(|handle| ParsedLink {
+            platform: recognizer.platform(),
+            handle,
+            url: url.to_owned(),
+        })
+    })
+}
+
+/// 根据作者的urls重建author_links，其他作者已使用相同账号时返回唯一约束错误
+pub async fn sync_author_links<I, S>(
+    conn: &mut AsyncPgConnection,
+    author_id: i32,
+    urls: I,
+) -> Result<(), diesel::result::Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut links: Vec<ParsedLink> = Vec::new();
+
+    for link in urls.into_iter().filter_map(|v| parse_link(v.as_ref())) {
+        if !links
+            .iter()
+            .any(|v| v.platform == link.platform && v.handle == link.handle)
+        {
+            links.push(link);
+        }
+    }
+
+    delete(schema::author_links::table.filter(schema::author_links::author_id.eq(author_id)))
+        .execute(conn)
+        .await?;
+
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    insert_into(schema::author_links::table)
+        .values(
+            links
+                .into_iter()
+                .map(|v| {
+                    (
+                        schema::author_links::author_id.eq(author_id),
+                        schema::author_links::platform.eq(v.platform),
+                        schema::author_links::handle.eq(v.handle),
+                        schema::author_links::url.eq(v.url),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// 重建全部作者的链接
+///
+/// 账号已被其他作者使用的作者会被跳过，返回重建的作者数量和被跳过的作者id
+pub async fn rebuild_author_links(
+    conn: &mut AsyncPgConnection,
+) -> Result<(i64, Vec<i32>), diesel::result::Error> {
+    let authors = schema::authors::table
+        .order(schema::authors::id.asc())
+        .select((schema::authors::id, schema::authors::urls))
+        .load::<(i32, Option<Vec<Option<String>>>)>(conn)
+        .await?;
+
+    let mut conflicts = Vec::new();
+
+    for (author_id, urls) in authors.iter() {
+        let urls = urls.to_owned().unwrap_or_default();
+
+        let result = conn
+            .transaction::<(), diesel::result::Error, _>(|conn| {
+                async move { sync_author_links(conn, *author_id, urls.iter().flatten()).await }
+                    .scope_boxed()
+            })
+            .await;
+
+        match result {
+            Ok(()) => {}
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                warn!("Author {} has links used by another author", author_id);
+                conflicts.push(*author_id);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((authors.len() as i64, conflicts))
+}
+
+/// 以平台账号查找作者
+pub async fn find_author_by_link(
+    conn: &mut AsyncPgConnection,
+    platform: i16,
+    handle: &str,
+) -> Result<Option<Author>, diesel::result::Error> {
+    schema::authors::table
+        .inner_join(schema::author_links::table)
+        .filter(schema::author_links::platform.eq(platform))
+        .filter(schema::author_links::handle.eq(handle))
+        .select(schema::authors::all_columns)
+        .first::<Author>(conn)
+        .await
+        .optional()
+}
+
+/// 作者的全部平台链接
+pub async fn load_author_links(
+    conn: &mut AsyncPgConnection,
+    author_id: i32,
+) -> Result<Vec<AuthorLink>, diesel::result::Error> {
+    schema::author_links::table
+        .filter(schema::author_links::author_id.eq(author_id))
+        .order(schema::author_links::id.asc())
+        .load::<AuthorLink>(conn)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recognize(recognizer: &dyn LinkRecognizer, url: &str) -> Option<String> {
+        recognizer.recognize(&Url::parse(url).unwrap())
+    }
+
+    fn assert_cases(recognizer: &dyn LinkRecognizer, cases: &[(&str, Option<&str>)]) {
+        for (url, expected) in cases {
+            assert_eq!(recognize(recognizer, url).as_deref(), *expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn pixiv() {
+        assert_cases(
+            &PixivRecognizer,
+            &[
+                ("https://www.pixiv.net/users/1234567", Some("1234567")),
+                (
+                    "https://pixiv.net/users/1234567/illustrations",
+                    Some("1234567"),
+                ),
+                ("https://www.pixiv.net/en/users/1234567", Some("1234567")),
+                (
+                    "https://www.pixiv.net/member.php?id=1234567",
+                    Some("1234567"),
+                ),
+                (
+                    "https://www.pixiv.net/member_illust.php?id=1234567&type=illust",
+                    Some("1234567"),
+                ),
+                ("https://www.pixiv.net/users/abc", None),
+                ("https://www.pixiv.net/artworks/112233445", None),
+                ("https://www.pixiv.net/member.php", None),
+                ("https://example.com/users/1234567", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn twitter() {
+        assert_cases(
+            &TwitterRecognizer,
+            &[
+                ("https://twitter.com/Sample_Artist", Some("sample_artist")),
+                (
+                    "https://www.twitter.com/sample_artist",
+                    Some("sample_artist"),
+                ),
+                (
+                    "https://mobile.twitter.com/Sample_Artist/media",
+                    Some("sample_artist"),
+                ),
+                ("https://x.com/@Sample_Artist", Some("sample_artist")),
+                ("https://mobile.x.com/sample_artist", Some("sample_artist")),
+                ("https://x.com/home", None),
+                ("https://x.com/i/web/status/1", None),
+                ("https://twitter.com/intent/follow?screen_name=a", None),
+                ("https://x.com/Search?q=a", None),
+                ("https://x.com/sample-artist", None),
+                ("https://x.com/a_very_long_handle_name", None),
+                ("https://x.com/", None),
+                ("https://example.com/sample_artist", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn fanbox() {
+        assert_cases(
+            &FanboxRecognizer,
+            &[
+                ("https://sample-artist.fanbox.cc/", Some("sample-artist")),
+                (
+                    "https://Sample-Artist.fanbox.cc/posts/1",
+                    Some("sample-artist"),
+                ),
+                ("https://www.fanbox.cc/@sample", Some("sample")),
+                ("https://fanbox.cc/@Sample/posts", Some("sample")),
+                ("https://fanbox.cc/sample", None),
+                ("https://api.fanbox.cc/post.info", None),
+                ("https://a.b.fanbox.cc/", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn skeb() {
+        assert_cases(
+            &SkebRecognizer,
+            &[
+                ("https://skeb.jp/@Sample_Artist", Some("sample_artist")),
+                ("https://www.skeb.jp/@sample/works/1", Some("sample")),
+                ("https://skeb.jp/sample", None),
+                ("https://skeb.jp/", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn danbooru() {
+        assert_cases(
+            &DanbooruRecognizer,
+            &[
+                (
+                    "https://danbooru.donmai.us/posts?tags=Sample_Artist",
+                    Some("sample_artist"),
+                ),
+                (
+                    "https://danbooru.donmai.us/posts?tags=sample%2Bartist%26co",
+                    Some("sample+artist&co"),
+                ),
+                ("https://danbooru.donmai.us/posts?tags=a+b", None),
+                ("https://danbooru.donmai.us/posts", None),
+                ("https://danbooru.donmai.us/posts/6543210", None),
+            ],
+        );
+    }
+
+    #[test]
+    fn website() {
+        assert_cases(
+            &WebsiteRecognizer,
+            &[
+                ("https://Example.com/", Some("example.com")),
+                ("http://www.example.com/blog/", Some("example.com/blog")),
+                (
+                    "https://www.youtube.com/watch?v=abc123",
+                    Some("youtube.com/watch?v=abc123"),
+                ),
+                (
+                    "https://www.youtube.com/watch?v=abc123&utm_source=x&si=y",
+                    Some("youtube.com/watch?v=abc123"),
+                ),
+                (
+                    "https://example.com/?utm_source=x&fbclid=y",
+                    Some("example.com"),
+                ),
+            ],
+        );
+
+        // 查询参数不同的个人网站不会被识别为同一账号
+        assert_ne!(
+            recognize(&WebsiteRecognizer, "https://www.youtube.com/watch?v=a"),
+            recognize(&WebsiteRecognizer, "https://www.youtube.com/watch?v=b")
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let cases = [
+            (
+                "https://www.pixiv.net/en/users/1234567",
+                Some((AuthorPlatform::Pixiv, "1234567")),
+            ),
+            (
+                " https://X.com/Sample_Artist ",
+                Some((AuthorPlatform::Twitter, "sample_artist")),
+            ),
+            (
+                "https://sample.fanbox.cc/",
+                Some((AuthorPlatform::Fanbox, "sample")),
+            ),
+            (
+                "https://skeb.jp/@sample",
+                Some((AuthorPlatform::Skeb, "sample")),
+            ),
+            (
+                "https://danbooru.donmai.us/posts?tags=sample",
+                Some((AuthorPlatform::Danbooru, "sample")),
+            ),
+            // 保留字路径交给个人网站处理
+            (
+                "https://x.com/home",
+                Some((AuthorPlatform::Website, "x.com/home")),
+            ),
+            (
+                "https://example.com/about/",
+                Some((AuthorPlatform::Website, "example.com/about")),
+            ),
+            ("ftp://example.com/", None),
+            ("mailto:sample@example.com", None),
+            ("not a url", None),
+        ];
+
+        for (url, expected) in cases {
+            let parsed = parse_link(url);
+
+            assert_eq!(
+                parsed.as_ref().map(|v| (v.platform, v.handle.as_str())),
+                expected.map(|(platform, handle)| (platform as i16, handle)),
+                "{}",
+                url
+            );
+
+            if let Some(parsed) = parsed {
+                assert_eq!(parsed.url, url.trim());
+            }
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::{fmt, io::Cursor};
+
+use aws_sdk_s3::{operation::put_object::PutObjectError, primitives::ByteStream};
+use chrono::{DateTime, Utc};
+use diesel::{insert_into, update, ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use futures::{stream, StreamExt};
+use image::{io::Reader as ImageReader, ImageError, ImageOutputFormat};
+use rocket::http::{ContentType, Status};
+use serde::Serialize;
+
+use crate::{
+    models::LocalFile,
+    schema,
+    utils::TransactionError,
+    web_fetch::{fetch_error_to_status, FetchError, WebFetcher},
+    BUCKET,
+};
+
+pub const IMAGE_PREFIX: &str = "image/";
+
+#[derive(Debug)]
+pub enum ImageProcessError {
+    Io(std::io::Error),
+    Decode(ImageError),
+    Encode(ImageError),
+    Interrupted,
+    ResultError(diesel::result::Error),
+}
+
+impl fmt::Display for ImageProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageProcessError::Io(err) => write!(f, "failed to read image: {}", err),
+            ImageProcessError::Decode(err) => write!(f, "failed to decode image: {}", err),
+            ImageProcessError::Encode(err) => write!(f, "failed to encode image: {}", err),
+            ImageProcessError::Interrupted => write!(f, "image processing was interrupted"),
+            ImageProcessError::ResultError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImageProcessError {}
+
+pub fn image_process_error_to_status(err: ImageProcessError) -> Status {
+    if let ImageProcessError::Decode(_) = err {
+        Status::BadRequest
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// 转换为WebP后等待存储的图片
+#[derive(Clone, Debug)]
+pub struct PreparedImage {
+    pub md5: String,
+    pub filename: String,
+    pub key: String,
+    pub data: Vec<u8>,
+    pub content_type: ContentType,
+    /// 转换前的格式，例如`image/png`
+    pub source_format: Option<String>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum PendingImage {
+    /// 相同内容的图片已经存在
+    Stored(String),
+    New(Box<PreparedImage>),
+}
+
+/// 解码图片并转换为WebP
+pub fn prepare_image(data: &[u8]) -> Result<PreparedImage, ImageProcessError> {
+    let mut new_data_vec: Vec<u8> = Vec::new();
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(ImageProcessError::Io)?;
+    let source_format = reader.format().map(|v| v.to_mime_type().to_owned());
+
+    let image = reader.decode().map_err(ImageProcessError::Decode)?;
+    image
+        .write_to(&mut Cursor::new(&mut new_data_vec), ImageOutputFormat::WebP)
+        .map_err(ImageProcessError::Encode)?;
+
+    let digest = md5::compute(&new_data_vec);
+    let md5 = format!("{:x}", digest);
+
+    let content_type = ContentType::WEBP;
+
+    let filename = if let Some(ext) = content_type.extension() {
+        format!("{}.{}", md5, ext.as_str())
+    } else {
+        md5.to_owned()
+    };
+
+    let key = format!("{}{}", IMAGE_PREFIX, filename);
+
+    Ok(PreparedImage {
+        md5,
+        filename,
+        key,
+        data: new_data_vec,
+        content_type,
+        source_format,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+/// 在阻塞线程中转换图片，并检查是否已存在相同的文件
+pub async fn prepare_pending_image(
+    conn: &mut AsyncPgConnection,
+    data: Vec<u8>,
+) -> Result<PendingImage, ImageProcessError> {
+    let prepared = prepare_image_blocking(data).await?;
+
+    find_pending_image(conn, prepared).await
+}
+
+/// 在阻塞线程中转换图片
+pub async fn prepare_image_blocking(data: Vec<u8>) -> Result<PreparedImage, ImageProcessError> {
+    tokio::task::spawn_blocking(move || prepare_image(&data))
+        .await
+        .map_err(|_| ImageProcessError::Interrupted)?
+}
+
+/// 检查是否已存在相同的文件
+pub async fn find_pending_image(
+    conn: &mut AsyncPgConnection,
+    prepared: PreparedImage,
+) -> Result<PendingImage, ImageProcessError> {
+    let existing = schema::local_files::table
+        .find(&prepared.md5)
+        .first::<LocalFile>(conn)
+        .await
+        .optional()
+        .map_err(ImageProcessError::ResultError)?;
+
+    Ok(match existing {
+        Some(local_file) => PendingImage::Stored(local_file.id),
+        None => PendingImage::New(Box::new(prepared)),
+    })
+}
+
+/// 网络图片的来源
+#[derive(Clone, Debug)]
+pub struct ImageSource {
+    pub url: String,
+    pub final_url: String,
+    pub fetched_at: DateTime<Utc>,
+    pub headers: serde_json::Value,
+    pub importer: Option<String>,
+}
+
+/// 下载的网络图片及其来源
+pub struct FetchedImage {
+    pub data: Vec<u8>,
+    pub source: ImageSource,
+}
+
+/// 写入local_files并上传到存储桶，返回与输入顺序一致的id
+pub async fn store_pending_images(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    pending_images: Vec<PendingImage>,
+) -> Result<Vec<String>, TransactionError<PutObjectError>> {
+    store_images(
+        conn,
+        s3_client,
+        pending_images.into_iter().map(|v| (v, None)).collect(),
+    )
+    .await
+}
+
+/// 与store_pending_images相同，同时记录图片的来源，
+/// 已存在的文件只在尚未记录来源时写入
+pub async fn store_fetched_images(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    pending_images: Vec<(PendingImage, ImageSource)>,
+) -> Result<Vec<String>, TransactionError<PutObjectError>> {
+    store_images(
+        conn,
+        s3_client,
+        pending_images
+            .into_iter()
+            .map(|(image, source)| (image, Some(source)))
+            .collect(),
+    )
+    .await
+}
+
+async fn store_images(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    pending_images: Vec<(PendingImage, Option<ImageSource>)>,
+) -> Result<Vec<String>, TransactionError<PutObjectError>> {
+    conn.transaction::<Vec<String>, TransactionError<PutObjectError>, _>(|conn| {
+        async move {
+            let mut stored_ids: Vec<String> = Vec::with_capacity(pending_images.len());
+            let mut new_images: Vec<(&PreparedImage, Option<&ImageSource>)> =
+                Vec::with_capacity(pending_images.len());
+            for (item, source) in &pending_images {
+                match item {
+                    PendingImage::Stored(id) => {
+                        if let Some(source) = source {
+                            update(schema::local_files::table.find(id))
+                                .filter(schema::local_files::source_url.is_null())
+                                .set((
+                                    schema::local_files::source_url.eq(&source.url),
+                                    schema::local_files::source_final_url.eq(&source.final_url),
+                                    schema::local_files::fetched_at.eq(&source.fetched_at),
+                                    schema::local_files::source_headers.eq(&source.headers),
+                                    schema::local_files::importer.eq(&source.importer),
+                                ))
+                                .execute(conn)
+                                .await
+                                .map_err(TransactionError::ResultError)?;
+                        }
+                        stored_ids.push(id.to_owned());
+                    }
+                    PendingImage::New(data) => {
+                        // 同一批次中可能包含相同的图片
+                        if !new_images.iter().any(|(v, _)| v.md5 == data.md5) {
+                            new_images.push((data, source.as_ref()));
+                        }
+                        stored_ids.push(data.md5.to_owned());
+                    }
+                }
+            }
+
+            if new_images.is_empty() {
+                return Ok(stored_ids);
+            }
+
+            insert_into(schema::local_files::table)
+                .values(
+                    new_images
+                        .iter()
+                        .map(|(data, source)| {
+                            (
+                                schema::local_files::id.eq(&data.md5),
+                                schema::local_files::file_name.eq(&data.filename),
+                                schema::local_files::path.eq(&data.key),
+                                schema::local_files::source_url.eq(source.map(|v| &v.url)),
+                                schema::local_files::source_final_url
+                                    .eq(source.map(|v| &v.final_url)),
+                                schema::local_files::fetched_at.eq(source.map(|v| v.fetched_at)),
+                                schema::local_files::source_headers.eq(source.map(|v| &v.headers)),
+                                schema::local_files::importer
+                                    .eq(source.and_then(|v| v.importer.as_ref())),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .execute(conn)
+                .await
+                .map_err(TransactionError::ResultError)?;
+
+            for (data, _) in new_images {
+                s3_client
+                    .put_object()
+                    .body(ByteStream::from(data.data.to_owned()))
+                    .bucket(BUCKET)
+                    .content_type(data.content_type.to_string())
+                    .content_length(data.data.len() as i64)
+                    .key(&data.key)
+                    .send()
+                    .await
+                    .map_err(TransactionError::SdkError)?;
+            }
+
+            Ok(stored_ids)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// 下载网络图片，以文件头判断内容是否为图片
+pub async fn fetch_image(web_fetcher: &WebFetcher, url: &str) -> Result<FetchedImage, FetchError> {
+    fetch_image_with_headers(web_fetcher, url, &[]).await
+}
+
+pub async fn fetch_image_with_headers(
+    web_fetcher: &WebFetcher,
+    url: &str,
+    headers: &[(String, String)],
+) -> Result<FetchedImage, FetchError> {
+    let fetched_at = Utc::now();
+    let response = web_fetcher.fetch_response(url, headers).await?;
+
+    image::guess_format(&response.data).map_err(|_| FetchError::NotImage)?;
+
+    Ok(FetchedImage {
+        data: response.data,
+        source: ImageSource {
+            url: url.trim().to_owned(),
+            final_url: response.final_url,
+            fetched_at,
+            headers: serde_json::Value::Object(
+                response
+                    .headers
+                    .into_iter()
+                    .map(|(name, value)| (name, serde_json::Value::String(value)))
+                    .collect(),
+            ),
+            importer: None,
+        },
+    })
+}
+
+/// 批量处理中单个图片的失败原因
+#[derive(Serialize, Debug, Clone)]
+pub struct ImageErrorDetail {
+    pub status: u16,
+    pub message: String,
+}
+
+impl From<Status> for ImageErrorDetail {
+    fn from(value: Status) -> Self {
+        Self {
+            status: value.code,
+            message: value.reason_lossy().to_owned(),
+        }
+    }
+}
+
+impl From<FetchError> for ImageErrorDetail {
+    fn from(value: FetchError) -> Self {
+        Self {
+            message: value.to_string(),
+            status: fetch_error_to_status(value).code,
+        }
+    }
+}
+
+impl From<ImageProcessError> for ImageErrorDetail {
+    fn from(value: ImageProcessError) -> Self {
+        Self {
+            message: value.to_string(),
+            status: image_process_error_to_status(value).code,
+        }
+    }
+}
+
+/// 批量存储中单个图片的结果，成功时id不为None
+#[derive(Debug, Clone)]
+pub struct BatchImageResult {
+    pub id: Option<String>,
+    /// 已存在相同的文件，或与同一批次中的其他图片重复
+    pub deduplicated: bool,
+    pub error: Option<ImageErrorDetail>,
+}
+
+/// 按顺序查找已存在的图片、标记同一批次中的重复图片并存储，结果与输入顺序一致
+///
+/// 单个图片失败不影响其他图片，atomic为true时任一图片失败则不存储任何图片，
+/// 只有写入数据库或存储桶失败时返回错误
+pub async fn store_image_batch(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    items: Vec<Result<(PreparedImage, Option<ImageSource>), ImageErrorDetail>>,
+    atomic: bool,
+) -> Result<Vec<BatchImageResult>, TransactionError<PutObjectError>> {
+    let mut results = Vec::with_capacity(items.len());
+    let mut pending_images = Vec::with_capacity(items.len());
+    let mut seen_md5: Vec<String> = Vec::new();
+
+    for item in items {
+        let pending = match item {
+            Ok((prepared, source)) => {
+                let md5 = prepared.md5.to_owned();
+                find_pending_image(conn, prepared)
+                    .await
+                    .map(|pending| (md5, pending, source))
+                    .map_err(ImageErrorDetail::from)
+            }
+            Err(err) => Err(err),
+        };
+
+        results.push(match pending {
+            Ok((md5, pending, source)) => {
+                let deduplicated =
+                    matches!(pending, PendingImage::Stored(_)) || seen_md5.contains(&md5);
+                seen_md5.push(md5);
+                pending_images.push((pending, source));
+
+                BatchImageResult {
+                    id: None,
+                    deduplicated,
+                    error: None,
+                }
+            }
+            Err(err) => BatchImageResult {
+                id: None,
+                deduplicated: false,
+                error: Some(err),
+            },
+        });
+    }
+
+    if atomic && results.iter().any(|v| v.error.is_some()) {
+        return Ok(results);
+    }
+
+    let mut stored_ids = store_images(conn, s3_client, pending_images)
+        .await?
+        .into_iter();
+
+    for result in results.iter_mut().filter(|v| v.error.is_none()) {
+        result.id = stored_ids.next();
+    }
+
+    Ok(results)
+}
+
+/// 批量导入中单个地址的结果，成功时id不为None
+#[derive(Serialize, Debug, Clone)]
+pub struct WebImportResult {
+    pub url: String,
+    pub id: Option<String>,
+    /// 已存在相同的文件，或与同一批次中的其他地址重复
+    pub deduplicated: bool,
+    pub error: Option<ImageErrorDetail>,
+}
+
+/// 并发下载并转换多个网络图片，单个地址失败不影响其他地址，
+/// 结果与输入顺序一致，只有写入数据库或存储桶失败时返回错误
+pub async fn import_web_images(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    web_fetcher: &WebFetcher,
+    urls: Vec<String>,
+) -> Result<Vec<WebImportResult>, TransactionError<PutObjectError>> {
+    let prepared = stream::iter(urls.to_owned())
+        .map(|url| async move {
+            let fetched = fetch_image(web_fetcher, &url).await?;
+            let prepared = prepare_image_blocking(fetched.data).await?;
+
+            Ok::<_, ImageErrorDetail>((prepared, fetched.source))
+        })
+        .buffered(web_fetcher.concurrency())
+        .collect::<Vec<_>>()
+        .await;
+
+    let stored = store_image_batch(
+        conn,
+        s3_client,
+        prepared
+            .into_iter()
+            .map(|v| v.map(|(prepared, source)| (prepared, Some(source))))
+            .collect(),
+        false,
+    )
+    .await?;
+
+    Ok(urls
+        .into_iter()
+        .zip(stored)
+        .map(|(url, stored)| WebImportResult {
+            url,
+            id: stored.id,
+            deduplicated: stored.deduplicated,
+            error: stored.error,
+        })
+        .collect())
+}
@@ -0,0 +1,183 @@
+use chrono::DateTime;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::misc::enums::AuthorPlatform;
+
+use super::{ImportError, Importer, SourceArtist, SourcePost, SourceRequest};
+
+#[derive(Deserialize)]
+struct Post {
+    created_at: String,
+    rating: Option<String>,
+    /// 受限的作品没有file_url
+    file_url: Option<String>,
+    tag_string_general: String,
+    tag_string_artist: String,
+    tag_string_copyright: String,
+    tag_string_character: String,
+}
+
+/// 空格分隔、以下划线代替空格的标签
+fn split_tags(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split_whitespace().map(|v| v.replace('_', " "))
+}
+
+pub struct DanbooruImporter;
+
+impl Importer for DanbooruImporter {
+    fn name(&self) -> &'static str {
+        "danbooru"
+    }
+
+    /// `danbooru.donmai.us/posts/<id>`
+    fn post_id(&self, url: &Url) -> Option<String> {
+        if url.host_str()? != "danbooru.donmai.us" {
+            return None;
+        }
+
+        let segments = url
+            .path_segments()?
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>();
+
+        match segments.as_slice() {
+            ["posts", id] if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+                Some(id.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn requests(&self, post_id: &str) -> Vec<SourceRequest> {
+        vec![SourceRequest::new(format!(
+            "https://danbooru.donmai.us/posts/{}.json",
+            post_id
+        ))]
+    }
+
+    fn parse(&self, post_id: &str, responses: &[Vec<u8>]) -> Result<SourcePost, ImportError> {
+        let [post] = responses else {
+            return Err(ImportError::Parse(
+                "unexpected number of responses".to_owned(),
+            ));
+        };
+
+        let post = serde_json::from_slice::<Post>(post)?;
+
+        // 多位画师时只关联第一位
+        let artist = post
+            .tag_string_artist
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| ImportError::Parse("post has no artist tag".to_owned()))?
+            .to_ascii_lowercase();
+
+        let tags = split_tags(&post.tag_string_copyright)
+            .chain(split_tags(&post.tag_string_character).map(|v| format!("character:{}", v)))
+            .chain(split_tags(&post.tag_string_general))
+            .collect();
+
+        // 画师标签可能包含`+`、`&`等字符，需要编码后写入查询参数
+        let mut artist_url = Url::parse("https://danbooru.donmai.us/posts")
+            .map_err(|err| ImportError::Parse(err.to_string()))?;
+        artist_url.query_pairs_mut().append_pair("tags", &artist);
+
+        Ok(SourcePost {
+            importer: self.name(),
+            url: format!("https://danbooru.donmai.us/posts/{}", post_id),
+            title: None,
+            artist: SourceArtist {
+                platform: AuthorPlatform::Danbooru as i16,
+                url: artist_url.to_string(),
+                name: Some(artist.replace('_', " ")),
+                handle: artist,
+            },
+            tags,
+            date: DateTime::parse_from_rfc3339(&post.created_at)
+                .ok()
+                .map(|v| v.date_naive()),
+            nsfw: matches!(post.rating.as_deref(), Some("q" | "e")),
+            images: post.file_url.into_iter().map(SourceRequest::new).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::author_links::parse_link;
+
+    const POST: &[u8] = include_bytes!("fixtures/danbooru_post.json");
+    const RESTRICTED: &[u8] = include_bytes!("fixtures/danbooru_restricted.json");
+
+    #[test]
+    fn post_id() {
+        let cases = [
+            ("https://danbooru.donmai.us/posts/6543210", Some("6543210")),
+            (
+                "https://danbooru.donmai.us/posts/6543210?q=touhou",
+                Some("6543210"),
+            ),
+            ("https://danbooru.donmai.us/posts?tags=touhou", None),
+            ("https://danbooru.donmai.us/posts/abc", None),
+            ("https://safebooru.donmai.us/posts/6543210", None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(
+                DanbooruImporter
+                    .post_id(&Url::parse(url).unwrap())
+                    .as_deref(),
+                expected,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let post = DanbooruImporter.parse("6543210", &[POST.to_vec()]).unwrap();
+
+        assert_eq!(post.importer, "danbooru");
+        assert_eq!(post.url, "https://danbooru.donmai.us/posts/6543210");
+        assert_eq!(post.artist.platform, AuthorPlatform::Danbooru as i16);
+        assert_eq!(post.artist.handle, "sample+artist&co");
+        assert_eq!(post.artist.name.as_deref(), Some("sample+artist&co"));
+        assert_eq!(
+            post.tags,
+            ["touhou", "character:hakurei reimu", "1girl", "long hair"]
+        );
+        // 使用投稿时所在时区的日期
+        assert_eq!(post.date, NaiveDate::from_ymd_opt(2023, 9, 1));
+        assert!(post.nsfw);
+        assert_eq!(
+            post.images,
+            [SourceRequest::new(
+                "https://cdn.donmai.us/original/01/23/0123456789abcdef0123456789abcdef.png"
+            )]
+        );
+    }
+
+    #[test]
+    fn artist_url_round_trip() {
+        let post = DanbooruImporter.parse("6543210", &[POST.to_vec()]).unwrap();
+        let link = parse_link(&post.artist.url).unwrap();
+
+        assert_eq!(link.platform, post.artist.platform);
+        assert_eq!(link.handle, post.artist.handle);
+    }
+
+    #[test]
+    fn parse_restricted() {
+        let post = DanbooruImporter
+            .parse("6543211", &[RESTRICTED.to_vec()])
+            .unwrap();
+
+        assert!(post.images.is_empty());
+        assert_eq!(post.tags, ["original", "1girl"]);
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use chrono::{NaiveDate, Utc};
+use diesel::{insert_into, ExpressionMethods};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use reqwest::Url;
+use rocket::http::Status;
+use serde::Serialize;
+
+use crate::{
+    author_links::{find_author_by_link, sync_author_links},
+    image_pipeline::{
+        fetch_image_with_headers, image_process_error_to_status, prepare_pending_image,
+        store_fetched_images, ImageProcessError,
+    },
+    routes::tags::normalize_tags,
+    schema,
+    utils::{naive_date_format_option, transaction_error_to_status, TransactionError},
+    web_fetch::{fetch_error_to_status, FetchError, WebFetcher},
+};
+
+mod danbooru;
+mod pixiv;
+mod twitter;
+
+/// 导入作品时需要请求的地址
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl SourceRequest {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+/// 作品的作者在来源站点上的账号
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceArtist {
+    pub platform: i16,
+    pub handle: String,
+    pub name: Option<String>,
+    /// 作者主页，新建作者时写入urls
+    pub url: String,
+}
+
+/// 从来源站点解析出的作品
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourcePost {
+    /// 导入器名称
+    pub importer: &'static str,
+    pub url: String,
+    pub title: Option<String>,
+    pub artist: SourceArtist,
+    pub tags: Vec<String>,
+    #[serde(with = "naive_date_format_option")]
+    pub date: Option<NaiveDate>,
+    pub nsfw: bool,
+    /// 原图地址及下载时需要的请求头
+    pub images: Vec<SourceRequest>,
+}
+
+/// 站点导入器
+///
+/// 导入器只负责生成请求和解析响应，不直接访问网络，
+/// 因此可以用保存下来的响应内容验证解析结果
+pub trait Importer: Sync {
+    fn name(&self) -> &'static str;
+
+    /// 从作品页面地址中提取作品id，不属于该站点时返回None
+    fn post_id(&self, url: &Url) -> Option<String>;
+
+    /// 获取作品信息需要请求的接口
+    fn requests(&self, post_id: &str) -> Vec<SourceRequest>;
+
+    /// 解析接口的响应，responses与requests的顺序一致
+    fn parse(&self, post_id: &str, responses: &[Vec<u8>]) -> Result<SourcePost, ImportError>;
+}
+
+const IMPORTERS: &[&dyn Importer] = &[
+    &pixiv::PixivImporter,
+    &twitter::TwitterImporter,
+    &danbooru::DanbooruImporter,
+];
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// 没有可以处理该地址的导入器
+    Unsupported,
+    Fetch(FetchError),
+    /// 来源站点返回的内容无法解析
+    Parse(String),
+    NoImages,
+    ImageProcess(ImageProcessError),
+    Store(Box<TransactionError<PutObjectError>>),
+    ResultError(diesel::result::Error),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Unsupported => write!(f, "unsupported url"),
+            ImportError::Fetch(err) => write!(f, "{}", err),
+            ImportError::Parse(message) => write!(f, "failed to parse source: {}", message),
+            ImportError::NoImages => write!(f, "post has no images"),
+            ImportError::ImageProcess(err) => write!(f, "{}", err),
+            ImportError::Store(err) => write!(f, "failed to store images: {:?}", err),
+            ImportError::ResultError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<FetchError> for ImportError {
+    fn from(value: FetchError) -> Self {
+        Self::Fetch(value)
+    }
+}
+
+impl From<diesel::result::Error> for ImportError {
+    fn from(value: diesel::result::Error) -> Self {
+        Self::ResultError(value)
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
+
+pub fn import_error_to_status(err: ImportError) -> Status {
+    match err {
+        ImportError::Unsupported | ImportError::NoImages => Status::UnprocessableEntity,
+        ImportError::Fetch(err) => fetch_error_to_status(err),
+        ImportError::Parse(_) => Status::BadGateway,
+        ImportError::ImageProcess(err) => image_process_error_to_status(err),
+        ImportError::Store(err) => transaction_error_to_status(*err),
+        ImportError::ResultError(_) => Status::InternalServerError,
+    }
+}
+
+/// 查找可以处理该地址的导入器
+pub fn find_importer(url: &str) -> Option<(&'static dyn Importer, String)> {
+    let url = Url::parse(url.trim()).ok()?;
+
+    IMPORTERS
+        .iter()
+        .find_map(|importer| importer.post_id(&url).map(|id| (*importer, id)))
+}
+
+/// 请求来源站点并解析作品信息
+pub async fn fetch_source_post(
+    web_fetcher: &WebFetcher,
+    url: &str,
+) -> Result<SourcePost, ImportError> {
+    let (importer, post_id) = find_importer(url).ok_or(ImportError::Unsupported)?;
+
+    let mut responses = Vec::new();
+    for request in importer.requests(&post_id) {
+        responses.push(
+            web_fetcher
+                .fetch_with_headers(&request.url, &request.headers)
+                .await?,
+        );
+    }
+
+    let post = importer.parse(&post_id, &responses)?;
+
+    if post.images.is_empty() {
+        return Err(ImportError::NoImages);
+    }
+
+    Ok(post)
+}
+
+/// 以来源站点的账号查找作者，不存在时新建
+async fn attach_author(
+    conn: &mut AsyncPgConnection,
+    artist: &SourceArtist,
+) -> Result<i32, diesel::result::Error> {
+    if let Some(author) = find_author_by_link(conn, artist.platform, &artist.handle).await? {
+        return Ok(author.id);
+    }
+
+    conn.transaction::<i32, diesel::result::Error, _>(|conn| {
+        async move {
+            let urls = vec![artist.url.to_owned()];
+
+            let author_id = insert_into(schema::authors::table)
+                .values((
+                    schema::authors::name.eq(artist.name.as_ref().unwrap_or(&artist.handle)),
+                    schema::authors::urls.eq(&urls),
+                ))
+                .returning(schema::authors::id)
+                .get_result::<i32>(conn)
+                .await?;
+
+            sync_author_links(conn, author_id, &urls).await?;
+
+            Ok(author_id)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// 导入来源站点的作品，下载全部图片并创建图片条目，返回图片条目的id
+pub async fn import_source_post(
+    conn: &mut AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    web_fetcher: &WebFetcher,
+    url: &str,
+) -> Result<i32, ImportError> {
+    let post = fetch_source_post(web_fetcher, url).await?;
+
+    let mut pending_images = Vec::with_capacity(post.images.len());
+    for image in &post.images {
+        let mut fetched = fetch_image_with_headers(web_fetcher, &image.url, &image.headers).await?;
+        fetched.source.importer = Some(post.importer.to_owned());
+
+        pending_images.push((
+            prepare_pending_image(conn, fetched.data)
+                .await
+                .map_err(ImportError::ImageProcess)?,
+            fetched.source,
+        ));
+    }
+
+    let local_file_ids = store_fetched_images(conn, s3_client, pending_images)
+        .await
+        .map_err(|err| ImportError::Store(Box::new(err)))?;
+
+    let author_id = attach_author(conn, &post.artist).await?;
+    let tags = normalize_tags(conn, &post.tags).await?;
+
+    let image_item_id = conn
+        .transaction::<i32, diesel::result::Error, _>(|conn| {
+            async move {
+                let image_item_id = insert_into(schema::image_items::table)
+                    .values((
+                        schema::image_items::author_id.eq(author_id),
+                        schema::image_items::urls.eq(vec![post.url.to_owned()]),
+                        schema::image_items::date
+                            .eq(post.date.unwrap_or_else(|| Utc::now().date_naive())),
+                        schema::image_items::nsfw.eq(post.nsfw),
+                        schema::image_items::tags.eq(&tags),
+                    ))
+                    .returning(schema::image_items::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+
+                insert_into(schema::image_items_local_files::table)
+                    .values(
+                        local_file_ids
+                            .iter()
+                            .map(|local_file_id| {
+                                (
+                                    schema::image_items_local_files::image_item_id
+                                        .eq(image_item_id),
+                                    schema::image_items_local_files::local_file_id
+                                        .eq(local_file_id),
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .execute(conn)
+                    .await?;
+
+                Ok(image_item_id)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(image_item_id)
+}
@@ -0,0 +1,221 @@
+use chrono::DateTime;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::misc::enums::AuthorPlatform;
+
+use super::{ImportError, Importer, SourceArtist, SourcePost, SourceRequest};
+
+/// pixiv的图片服务器会检查Referer
+const REFERER: &str = "https://www.pixiv.net/";
+
+#[derive(Deserialize)]
+struct Response<T> {
+    error: bool,
+    #[serde(default)]
+    message: String,
+    body: Option<T>,
+}
+
+impl<T> Response<T> {
+    fn into_body(self) -> Result<T, ImportError> {
+        match self.body {
+            Some(body) if !self.error => Ok(body),
+            _ => Err(ImportError::Parse(self.message)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Illust {
+    illust_title: String,
+    user_id: String,
+    user_name: String,
+    create_date: String,
+    x_restrict: i32,
+    tags: IllustTags,
+}
+
+#[derive(Deserialize)]
+struct IllustTags {
+    tags: Vec<IllustTag>,
+}
+
+#[derive(Deserialize)]
+struct IllustTag {
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    urls: PageUrls,
+}
+
+#[derive(Deserialize)]
+struct PageUrls {
+    original: String,
+}
+
+pub struct PixivImporter;
+
+impl Importer for PixivImporter {
+    fn name(&self) -> &'static str {
+        "pixiv"
+    }
+
+    /// `pixiv.net/artworks/<id>`、`pixiv.net/en/artworks/<id>`和旧的`member_illust.php?illust_id=<id>`
+    fn post_id(&self, url: &Url) -> Option<String> {
+        if !matches!(url.host_str()?, "pixiv.net" | "www.pixiv.net") {
+            return None;
+        }
+
+        let segments = url
+            .path_segments()?
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>();
+
+        let id = match segments.as_slice() {
+            ["artworks", id] | [_, "artworks", id] => id.to_string(),
+            ["member_illust.php"] => url
+                .query_pairs()
+                .find(|(k, _)| k == "illust_id")
+                .map(|(_, v)| v.into_owned())?,
+            _ => return None,
+        };
+
+        (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then_some(id)
+    }
+
+    fn requests(&self, post_id: &str) -> Vec<SourceRequest> {
+        vec![
+            SourceRequest::new(format!("https://www.pixiv.net/ajax/illust/{}", post_id))
+                .header("Referer", REFERER),
+            SourceRequest::new(format!(
+                "https://www.pixiv.net/ajax/illust/{}/pages",
+                post_id
+            ))
+            .header("Referer", REFERER),
+        ]
+    }
+
+    fn parse(&self, post_id: &str, responses: &[Vec<u8>]) -> Result<SourcePost, ImportError> {
+        let [illust, pages] = responses else {
+            return Err(ImportError::Parse(
+                "unexpected number of responses".to_owned(),
+            ));
+        };
+
+        let illust = serde_json::from_slice::<Response<Illust>>(illust)?.into_body()?;
+        let pages = serde_json::from_slice::<Response<Vec<Page>>>(pages)?.into_body()?;
+
+        Ok(SourcePost {
+            importer: self.name(),
+            url: format!("https://www.pixiv.net/artworks/{}", post_id),
+            title: Some(illust.illust_title).filter(|v| !v.is_empty()),
+            artist: SourceArtist {
+                platform: AuthorPlatform::Pixiv as i16,
+                url: format!("https://www.pixiv.net/users/{}", illust.user_id),
+                handle: illust.user_id,
+                name: Some(illust.user_name),
+            },
+            tags: illust.tags.tags.into_iter().map(|v| v.tag).collect(),
+            // 使用投稿时所在时区的日期
+            date: DateTime::parse_from_rfc3339(&illust.create_date)
+                .ok()
+                .map(|v| v.date_naive()),
+            nsfw: illust.x_restrict > 0,
+            images: pages
+                .into_iter()
+                .map(|v| SourceRequest::new(v.urls.original).header("Referer", REFERER))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    const ILLUST: &[u8] = include_bytes!("fixtures/pixiv_illust.json");
+    const PAGES: &[u8] = include_bytes!("fixtures/pixiv_pages.json");
+    const ERROR: &[u8] = include_bytes!("fixtures/pixiv_error.json");
+
+    #[test]
+    fn post_id() {
+        let cases = [
+            (
+                "https://www.pixiv.net/artworks/112233445",
+                Some("112233445"),
+            ),
+            ("https://pixiv.net/en/artworks/112233445", Some("112233445")),
+            (
+                "https://www.pixiv.net/member_illust.php?mode=medium&illust_id=112233445",
+                Some("112233445"),
+            ),
+            ("https://www.pixiv.net/users/1234567", None),
+            ("https://www.pixiv.net/artworks/abc", None),
+            ("https://example.com/artworks/112233445", None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(
+                PixivImporter.post_id(&Url::parse(url).unwrap()).as_deref(),
+                expected,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let post = PixivImporter
+            .parse("112233445", &[ILLUST.to_vec(), PAGES.to_vec()])
+            .unwrap();
+
+        assert_eq!(post.importer, "pixiv");
+        assert_eq!(post.url, "https://www.pixiv.net/artworks/112233445");
+        assert_eq!(post.title.as_deref(), Some("夏の終わり"));
+        assert_eq!(
+            post.artist,
+            SourceArtist {
+                platform: AuthorPlatform::Pixiv as i16,
+                handle: "1234567".to_owned(),
+                name: Some("サンプル作者".to_owned()),
+                url: "https://www.pixiv.net/users/1234567".to_owned(),
+            }
+        );
+        assert_eq!(post.tags, ["オリジナル", "女の子", "夏"]);
+        // 投稿时间为日本时间
+        assert_eq!(post.date, NaiveDate::from_ymd_opt(2023, 8, 31));
+        assert!(post.nsfw);
+        assert_eq!(
+            post.images,
+            [
+                SourceRequest::new(
+                    "https://i.pximg.net/img-original/img/2023/08/31/23/30/00/112233445_p0.png"
+                )
+                .header("Referer", REFERER),
+                SourceRequest::new(
+                    "https://i.pximg.net/img-original/img/2023/08/31/23/30/00/112233445_p1.jpg"
+                )
+                .header("Referer", REFERER),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_error_response() {
+        assert!(matches!(
+            PixivImporter.parse("1", &[ERROR.to_vec(), PAGES.to_vec()]),
+            Err(ImportError::Parse(_))
+        ));
+        assert!(matches!(
+            PixivImporter.parse("1", &[ILLUST.to_vec()]),
+            Err(ImportError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,326 @@
+use std::f64::consts::PI;
+
+use chrono::DateTime;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::misc::enums::AuthorPlatform;
+
+use super::{ImportError, Importer, SourceArtist, SourcePost, SourceRequest};
+
+#[derive(Deserialize)]
+struct Tweet {
+    created_at: String,
+    user: User,
+    #[serde(default)]
+    possibly_sensitive: bool,
+    #[serde(default, rename = "mediaDetails")]
+    media_details: Vec<Media>,
+    #[serde(default)]
+    entities: Entities,
+}
+
+#[derive(Deserialize)]
+struct User {
+    name: String,
+    screen_name: String,
+}
+
+#[derive(Deserialize)]
+struct Media {
+    #[serde(rename = "type")]
+    kind: String,
+    media_url_https: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Entities {
+    #[serde(default)]
+    hashtags: Vec<Hashtag>,
+}
+
+#[derive(Deserialize)]
+struct Hashtag {
+    text: String,
+}
+
+/// 与V8的`Number.prototype.toString(radix)`结果一致的小数转换
+fn to_radix_string(value: f64, radix: u32) -> String {
+    let next_double = |v: f64| f64::from_bits(v.to_bits() + 1);
+
+    let mut integer = value.floor();
+    let mut fraction = value - integer;
+    // 只计算到输入精度以内的小数位
+    let mut delta = (0.5 * (next_double(value) - value)).max(next_double(0.0));
+    let radix_f = radix as f64;
+
+    let mut fraction_digits: Vec<u32> = Vec::new();
+    if fraction >= delta {
+        loop {
+            fraction *= radix_f;
+            delta *= radix_f;
+            let digit = fraction as u32;
+            fraction_digits.push(digit);
+            fraction -= digit as f64;
+
+            if (fraction > 0.5 || (fraction == 0.5 && digit & 1 == 1)) && fraction + delta > 1.0 {
+                // 向前进位
+                loop {
+                    match fraction_digits.pop() {
+                        None => {
+                            integer += 1.0;
+                            break;
+                        }
+                        Some(digit) if digit + 1 < radix => {
+                            fraction_digits.push(digit + 1);
+                            break;
+                        }
+                        Some(_) => {}
+                    }
+                }
+                break;
+            }
+
+            if fraction < delta {
+                break;
+            }
+        }
+    }
+
+    let mut integer_digits: Vec<u32> = Vec::new();
+    loop {
+        let remainder = integer % radix_f;
+        integer_digits.push(remainder as u32);
+        integer = (integer - remainder) / radix_f;
+        if integer <= 0.0 {
+            break;
+        }
+    }
+
+    let digit_char = |v: &u32| char::from_digit(*v, radix).unwrap_or('0');
+
+    let mut output = integer_digits
+        .iter()
+        .rev()
+        .map(digit_char)
+        .collect::<String>();
+    if !fraction_digits.is_empty() {
+        output.push('.');
+        output.extend(fraction_digits.iter().map(digit_char));
+    }
+
+    output
+}
+
+/// 嵌入推文接口需要的token，由推文id计算得到
+fn syndication_token(post_id: &str) -> Option<String> {
+    let id = post_id.parse::<u64>().ok()? as f64;
+
+    Some(
+        to_radix_string(id / 1e15 * PI, 36)
+            .chars()
+            .filter(|c| *c != '0' && *c != '.')
+            .collect(),
+    )
+}
+
+pub struct TwitterImporter;
+
+impl Importer for TwitterImporter {
+    fn name(&self) -> &'static str {
+        "twitter"
+    }
+
+    /// `twitter.com/<handle>/status/<id>`或`x.com/<handle>/status/<id>`
+    fn post_id(&self, url: &Url) -> Option<String> {
+        if !matches!(
+            url.host_str()?.trim_start_matches("www."),
+            "twitter.com" | "mobile.twitter.com" | "x.com" | "mobile.x.com"
+        ) {
+            return None;
+        }
+
+        let segments = url
+            .path_segments()?
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<&str>>();
+
+        match segments.as_slice() {
+            [_, "status", id, ..] | ["i", "web", "status", id, ..]
+                if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                Some(id.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn requests(&self, post_id: &str) -> Vec<SourceRequest> {
+        vec![SourceRequest::new(format!(
+            "https://cdn.syndication.twimg.com/tweet-result?id={}&token={}",
+            post_id,
+            syndication_token(post_id).unwrap_or_default()
+        ))]
+    }
+
+    fn parse(&self, post_id: &str, responses: &[Vec<u8>]) -> Result<SourcePost, ImportError> {
+        let [tweet] = responses else {
+            return Err(ImportError::Parse(
+                "unexpected number of responses".to_owned(),
+            ));
+        };
+
+        let tweet = serde_json::from_slice::<Tweet>(tweet)?;
+        let handle = tweet.user.screen_name.to_ascii_lowercase();
+
+        Ok(SourcePost {
+            importer: self.name(),
+            url: format!(
+                "https://x.com/{}/status/{}",
+                tweet.user.screen_name, post_id
+            ),
+            title: None,
+            artist: SourceArtist {
+                platform: AuthorPlatform::Twitter as i16,
+                url: format!("https://x.com/{}", tweet.user.screen_name),
+                handle,
+                name: Some(tweet.user.name),
+            },
+            tags: tweet
+                .entities
+                .hashtags
+                .into_iter()
+                .map(|v| v.text)
+                .collect(),
+            date: DateTime::parse_from_rfc3339(&tweet.created_at)
+                .ok()
+                .map(|v| v.date_naive()),
+            nsfw: tweet.possibly_sensitive,
+            // 视频和动图只有预览图，不导入
+            images: tweet
+                .media_details
+                .into_iter()
+                .filter(|v| v.kind == "photo")
+                .map(|v| SourceRequest::new(format!("{}?name=orig", v.media_url_https)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    const TWEET: &[u8] = include_bytes!("fixtures/twitter_tweet.json");
+    const TOMBSTONE: &[u8] = include_bytes!("fixtures/twitter_tombstone.json");
+
+    #[test]
+    fn radix_string() {
+        // 与V8中`value.toString(36)`的结果对照
+        let cases = [
+            (5.5, "5.i"),
+            (0.5, "0.i"),
+            (1.0 / 3.0, "0.c"),
+            (123.456, "3f.gez4w97ry"),
+            (1e-7, "0.000061oezo085tl"),
+            (0.0, "0"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(to_radix_string(value, 36), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn token() {
+        let cases = [
+            ("1697654321098765432", "445c6hr5jsn"),
+            ("1580661436132757506", "3txslhgbjje"),
+            ("1234567890123456789", "2zqic77uqyk"),
+            ("1843212345678901234", "4gumelup6z"),
+            ("20", "6dq1a2xwd93"),
+            ("1", "bhi2ay3f28n"),
+        ];
+
+        for (id, expected) in cases {
+            assert_eq!(syndication_token(id).as_deref(), Some(expected), "{}", id);
+        }
+
+        assert_eq!(syndication_token("abc"), None);
+    }
+
+    #[test]
+    fn post_id() {
+        let cases = [
+            (
+                "https://x.com/Sample_Artist/status/1697654321098765432",
+                Some("1697654321098765432"),
+            ),
+            (
+                "https://twitter.com/Sample_Artist/status/1697654321098765432/photo/1",
+                Some("1697654321098765432"),
+            ),
+            (
+                "https://mobile.twitter.com/i/web/status/1697654321098765432",
+                Some("1697654321098765432"),
+            ),
+            ("https://www.x.com/Sample_Artist", None),
+            ("https://x.com/Sample_Artist/status/abc", None),
+            ("https://example.com/a/status/1", None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(
+                TwitterImporter
+                    .post_id(&Url::parse(url).unwrap())
+                    .as_deref(),
+                expected,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let post = TwitterImporter
+            .parse("1697654321098765432", &[TWEET.to_vec()])
+            .unwrap();
+
+        assert_eq!(post.importer, "twitter");
+        assert_eq!(
+            post.url,
+            "https://x.com/Sample_Artist/status/1697654321098765432"
+        );
+        assert_eq!(
+            post.artist,
+            SourceArtist {
+                platform: AuthorPlatform::Twitter as i16,
+                handle: "sample_artist".to_owned(),
+                name: Some("サンプル作者".to_owned()),
+                url: "https://x.com/Sample_Artist".to_owned(),
+            }
+        );
+        assert_eq!(post.tags, ["落書き", "オリジナル"]);
+        assert_eq!(post.date, NaiveDate::from_ymd_opt(2023, 9, 1));
+        assert!(!post.nsfw);
+        // 视频的预览图被跳过
+        assert_eq!(
+            post.images,
+            [
+                SourceRequest::new("https://pbs.twimg.com/media/F5AbCdEfGhIjKlM.jpg?name=orig"),
+                SourceRequest::new("https://pbs.twimg.com/media/F5NoPqRsTuVwXyZ.png?name=orig"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tombstone() {
+        assert!(matches!(
+            TwitterImporter.parse("1", &[TOMBSTONE.to_vec()]),
+            Err(ImportError::Parse(_))
+        ));
+    }
+}
@@ -11,16 +11,24 @@ use dotenvy::dotenv;
 use env_logger;
 use rocket::data::ToByteUnit;
 use std::env;
-use reqwest;
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
+use web_fetch::{WebFetchConfig, WebFetcher};
 
+mod author_links;
 mod db;
+mod image_pipeline;
+mod importers;
+mod markdown;
 mod misc;
 mod models;
+mod novel_export;
+mod novel_format;
+mod novel_metadata;
 mod schedule_jobs;
 mod schema;
+mod tag_query;
+mod task_queue;
 mod utils;
+mod web_fetch;
 
 mod routes;
 
@@ -30,7 +38,7 @@ pub struct AppState {
     pub database_url: String,
     pub jwt_signing_key: String,
     pub s3_client: aws_sdk_s3::Client,
-    pub reqwest_client: ClientWithMiddleware,
+    pub web_fetcher: WebFetcher,
 }
 
 pub async fn create_s3_client() -> aws_sdk_s3::Client {
@@ -52,17 +60,13 @@ async fn main() -> Result<(), rocket::Error> {
 
     let s3_client = create_s3_client().await;
 
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-
-    let reqwest_client = ClientBuilder::new(reqwest::Client::new())
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
+    let web_fetcher = WebFetcher::new(WebFetchConfig::from_env());
 
     let app_state = AppState {
         database_url: env::var("DATABASE_URL").expect("未设置DATABASE_URL"),
         jwt_signing_key: env::var("JWT_SIGNING_KEY").expect("未设置JWT_SIGNING_KEY"),
         s3_client,
-        reqwest_client,
+        web_fetcher,
     };
 
     let pool = db::establish_connection(app_state.database_url.to_owned()).await;
@@ -77,17 +81,43 @@ async fn main() -> Result<(), rocket::Error> {
         ..rocket::Config::default()
     };
 
-    schedule_jobs::init(app_state.database_url.to_owned()).await;
+    let job_registry = schedule_jobs::JobRegistry::new(
+        schedule_jobs::JobContext {
+            db: pool.clone(),
+            s3_client: app_state.s3_client.clone(),
+        },
+        schedule_jobs::LeaseConfig::from_env(),
+    );
+
+    schedule_jobs::init(job_registry.clone()).await;
+
+    let task_queue = task_queue::TaskQueue::new(
+        task_queue::TaskContext {
+            db: pool.clone(),
+            s3_client: app_state.s3_client.clone(),
+            web_fetcher: app_state.web_fetcher.clone(),
+        },
+        task_queue::TaskQueueConfig::from_env(),
+    );
+
+    task_queue.start_workers();
 
     rocket::custom(&config)
         .manage(pool)
         .manage(app_state)
+        .manage(job_registry)
+        .manage(task_queue)
         .mount("/api/authors", routes::authors::routes())
         .mount("/api/images", routes::images::routes())
         .mount("/api/auth", routes::auth::routes())
         .mount("/api/storage/image", routes::storage::image::routes())
         .mount("/api/storage/content", routes::storage::content::routes())
         .mount("/api/novels", routes::novels::routes())
+        .mount("/api/novels", routes::novel_chapters::routes())
+        .mount("/api/series", routes::series::routes())
+        .mount("/api/tags", routes::tags::routes())
+        .mount("/api/admin/jobs", routes::admin::jobs::routes())
+        .mount("/api/tasks", routes::tasks::routes())
         .ignite()
         .await?
         .launch()
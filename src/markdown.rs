@@ -0,0 +1,510 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
+
+use diesel::{insert_into, ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use pulldown_cmark::{escape::escape_html, html, CowStr, Event, Options, Parser, Tag};
+use uuid::Uuid;
+
+use crate::{models::LocalFile, schema};
+
+/// 渲染逻辑变化时递增，使已缓存的结果失效
+const RENDER_VERSION: u32 = 1;
+
+/// 引用local_files的图片地址前缀，例如`![说明](local-file:<id>)`
+pub const EMBED_SCHEME: &str = "local-file:";
+
+const SPOILER_OPEN: &str = ":::spoiler";
+const SPOILER_CLOSE: &str = ":::";
+const DEFAULT_SPOILER_TITLE: &str = "剧透";
+
+/// 嵌入文件的公开访问地址前缀，未设置时使用以`/`开头的相对地址
+fn storage_public_url() -> String {
+    env::var("STORAGE_PUBLIC_URL")
+        .map(|v| v.trim_end_matches('/').to_owned())
+        .unwrap_or_default()
+}
+
+fn options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options
+}
+
+fn escape(value: &str) -> String {
+    let mut output = String::new();
+    // 写入String不会失败
+    let _ = escape_html(&mut output, value);
+    output
+}
+
+/// 只允许http、https、mailto和相对地址
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim();
+
+    match url.find([':', '/', '?', '#']) {
+        Some(index) if url[index..].starts_with(':') => {
+            let scheme = url[..index].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// 将`:::spoiler 标题`和`:::`之间的内容替换为带有随机标记的注释，
+/// 渲染时只有带有该标记的注释会被转换为折叠块，用户输入的HTML不受影响
+fn mark_spoilers(source: &str, nonce: &str) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(source.len());
+    let mut titles = Vec::new();
+    let mut depth = 0;
+    let mut fence: Option<&str> = None;
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if let Some(title) = line.strip_prefix(SPOILER_OPEN) {
+            if title.is_empty() || title.starts_with(char::is_whitespace) {
+                output.push_str(&format!(
+                    "\n<!--spoiler-{}-open-{}-->\n\n",
+                    nonce,
+                    titles.len()
+                ));
+                titles.push(title.trim().to_owned());
+                depth += 1;
+                continue;
+            }
+        } else if line.trim_end() == SPOILER_CLOSE && depth > 0 {
+            output.push_str(&format!("\n<!--spoiler-{}-close-->\n\n", nonce));
+            depth -= 1;
+            continue;
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    for _ in 0..depth {
+        output.push_str(&format!("\n<!--spoiler-{}-close-->\n\n", nonce));
+    }
+
+    (output, titles)
+}
+
+/// 将`{漢字|かんじ}`转换为ruby注音，其余文本原样转义
+fn render_ruby(text: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut rest = text;
+    let mut found = false;
+
+    while let Some(start) = rest.find('{') {
+        let candidate = &rest[start + 1..];
+
+        let annotation = candidate.find('}').and_then(|end| {
+            let (base, reading) = candidate[..end].split_once('|')?;
+
+            if base.is_empty()
+                || reading.is_empty()
+                || base.contains(['{', '|'])
+                || reading.contains('{')
+            {
+                return None;
+            }
+
+            Some((base, reading, end))
+        });
+
+        match annotation {
+            Some((base, reading, end)) => {
+                output.push_str(&escape(&rest[..start]));
+                output.push_str(&format!(
+                    "<ruby>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
+                    escape(base),
+                    escape(reading)
+                ));
+                rest = &candidate[end + 1..];
+                found = true;
+            }
+            None => {
+                output.push_str(&escape(&rest[..start + 1]));
+                rest = candidate;
+            }
+        }
+    }
+
+    output.push_str(&escape(rest));
+
+    found.then_some(output)
+}
+
+/// 文本中引用的local_files id
+pub fn embed_ids(source: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+
+    for event in Parser::new_ext(source, options()) {
+        if let Event::Start(Tag::Image(_, url, _)) = event {
+            if let Some(id) = url.strip_prefix(EMBED_SCHEME) {
+                if !ids.iter().any(|v| v == id) {
+                    ids.push(id.to_owned());
+                }
+            }
+        }
+    }
+
+    ids
+}
+
+/// 将Markdown渲染为HTML
+///
+/// 支持脚注、ruby注音、折叠块和引用local_files的图片，原始HTML会被转义，
+/// 不安全的链接地址会被移除，embeds为已读取的嵌入文件
+pub fn render_markdown(source: &str, embeds: &HashMap<String, LocalFile>) -> String {
+    let public_url = storage_public_url();
+
+    render(source, |id| {
+        embeds.get(id).map(|v| format!("{}/{}", public_url, v.path))
+    })
+}
+
+/// 将Markdown渲染为XHTML片段，用于导出的电子书
+///
+/// 输出的空元素均为自闭合形式，embed_hrefs为嵌入文件在电子书内的地址，
+/// 不在其中的嵌入图片只保留说明文字
+pub fn render_markdown_xhtml(source: &str, embed_hrefs: &HashMap<String, String>) -> String {
+    render(source, |id| embed_hrefs.get(id).cloned())
+}
+
+/// embed_url返回嵌入文件的地址，无法显示时返回None
+fn render(source: &str, embed_url: impl Fn(&str) -> Option<String>) -> String {
+    let nonce = Uuid::new_v4().simple().to_string();
+    let (source, titles) = mark_spoilers(source, &nonce);
+    let open_prefix = format!("<!--spoiler-{}-open-", nonce);
+    let close_marker = format!("<!--spoiler-{}-close-->", nonce);
+
+    let mut events: Vec<Event> = Vec::new();
+    let mut in_code_block = false;
+    let mut dropped_images = 0;
+
+    for event in Parser::new_ext(&source, options()) {
+        let event = match event {
+            Event::Html(html) => {
+                let marker = html.trim();
+
+                if let Some(index) = marker
+                    .strip_prefix(open_prefix.as_str())
+                    .and_then(|v| v.strip_suffix("-->"))
+                    .and_then(|v| v.parse::<usize>().ok())
+                {
+                    let title = titles
+                        .get(index)
+                        .filter(|v| !v.is_empty())
+                        .map(|v| v.as_str())
+                        .unwrap_or(DEFAULT_SPOILER_TITLE);
+
+                    Event::Html(CowStr::from(format!(
+                        "<details class=\"spoiler\"><summary>{}</summary>\n",
+                        escape(title)
+                    )))
+                } else if marker == close_marker {
+                    Event::Html(CowStr::from("</details>\n"))
+                } else {
+                    Event::Text(html)
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code_block = true;
+                Event::Start(Tag::CodeBlock(kind))
+            }
+            Event::End(Tag::CodeBlock(kind)) => {
+                in_code_block = false;
+                Event::End(Tag::CodeBlock(kind))
+            }
+            Event::Start(Tag::Link(kind, url, title)) => {
+                let url = if is_safe_url(&url) {
+                    url
+                } else {
+                    CowStr::from("")
+                };
+                Event::Start(Tag::Link(kind, url, title))
+            }
+            Event::Start(Tag::Image(kind, url, title)) => {
+                let url = match url.strip_prefix(EMBED_SCHEME) {
+                    Some(id) => embed_url(id).map(CowStr::from),
+                    None if is_safe_url(&url) => Some(url),
+                    None => None,
+                };
+
+                match url {
+                    Some(url) => Event::Start(Tag::Image(kind, url, title)),
+                    // 无法显示的图片只保留说明文字
+                    None => {
+                        dropped_images += 1;
+                        continue;
+                    }
+                }
+            }
+            Event::End(Tag::Image(kind, url, title)) => {
+                if dropped_images > 0 {
+                    dropped_images -= 1;
+                    continue;
+                }
+                Event::End(Tag::Image(kind, url, title))
+            }
+            event => event,
+        };
+
+        // 合并相邻的文本，便于识别跨越多个事件的注音
+        match (events.last_mut(), event) {
+            (Some(Event::Text(last)), Event::Text(text)) if !in_code_block => {
+                *last = CowStr::from(format!("{}{}", last, text));
+            }
+            (_, event) => events.push(event),
+        }
+    }
+
+    let mut in_code_block = false;
+    let events = events.into_iter().map(|event| match event {
+        Event::Start(Tag::CodeBlock(kind)) => {
+            in_code_block = true;
+            Event::Start(Tag::CodeBlock(kind))
+        }
+        Event::End(Tag::CodeBlock(kind)) => {
+            in_code_block = false;
+            Event::End(Tag::CodeBlock(kind))
+        }
+        Event::Text(text) if !in_code_block => match render_ruby(&text) {
+            Some(html) => Event::Html(CowStr::from(html)),
+            None => Event::Text(text),
+        },
+        event => event,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    output
+}
+
+/// 渲染并缓存结果，相同内容只渲染一次
+///
+/// 缓存的key包含嵌入文件的id和路径，嵌入的文件被创建或删除后会重新渲染
+pub async fn render_markdown_cached(
+    conn: &mut AsyncPgConnection,
+    source: &str,
+) -> Result<String, diesel::result::Error> {
+    Ok(render_markdown_cached_batch(conn, &[source])
+        .await?
+        .remove(0))
+}
+
+/// 批量渲染并缓存结果，结果与sources的顺序一致
+///
+/// 嵌入文件、已缓存的结果和新的结果各只需要一次查询
+pub async fn render_markdown_cached_batch(
+    conn: &mut AsyncPgConnection,
+    sources: &[&str],
+) -> Result<Vec<String>, diesel::result::Error> {
+    if sources.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let source_embed_ids = sources
+        .iter()
+        .map(|v| embed_ids(v))
+        .collect::<Vec<Vec<String>>>();
+
+    let all_ids = source_embed_ids
+        .iter()
+        .flatten()
+        .collect::<HashSet<&String>>();
+    let embeds: HashMap<String, LocalFile> = if all_ids.is_empty() {
+        HashMap::new()
+    } else {
+        schema::local_files::table
+            .filter(schema::local_files::id.eq_any(all_ids))
+            .load::<LocalFile>(conn)
+            .await?
+            .into_iter()
+            .map(|v| (v.id.to_owned(), v))
+            .collect()
+    };
+
+    let public_url = storage_public_url();
+    let hashes = sources
+        .iter()
+        .zip(&source_embed_ids)
+        .map(|(source, ids)| {
+            let resolved = ids
+                .iter()
+                .map(|id| match embeds.get(id) {
+                    Some(local_file) => format!("{}={}", id, local_file.path),
+                    None => format!("{}=", id),
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            format!(
+                "{:x}",
+                md5::compute(format!(
+                    "{}\n{}\n{}\n{}",
+                    RENDER_VERSION, public_url, resolved, source
+                ))
+            )
+        })
+        .collect::<Vec<String>>();
+
+    let mut cached: HashMap<String, String> = schema::rendered_markdown::table
+        .filter(schema::rendered_markdown::hash.eq_any(&hashes))
+        .select((
+            schema::rendered_markdown::hash,
+            schema::rendered_markdown::html,
+        ))
+        .load::<(String, String)>(conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut rendered: Vec<(&String, String)> = Vec::new();
+    for (source, hash) in sources.iter().zip(&hashes) {
+        if !cached.contains_key(hash) {
+            let html = render_markdown(source, &embeds);
+            cached.insert(hash.to_owned(), html.to_owned());
+            rendered.push((hash, html));
+        }
+    }
+
+    if !rendered.is_empty() {
+        insert_into(schema::rendered_markdown::table)
+            .values(
+                rendered
+                    .iter()
+                    .map(|(hash, html)| {
+                        (
+                            schema::rendered_markdown::hash.eq(*hash),
+                            schema::rendered_markdown::html.eq(html),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(hashes
+        .iter()
+        .map(|hash| cached.get(hash).cloned().unwrap_or_default())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn render(source: &str) -> String {
+        render_markdown(source, &HashMap::new())
+    }
+
+    fn local_file(id: &str, path: &str) -> LocalFile {
+        LocalFile {
+            id: id.to_owned(),
+            file_name: None,
+            path: path.to_owned(),
+            created_at: Utc::now(),
+            source_url: None,
+            source_final_url: None,
+            fetched_at: None,
+            source_headers: None,
+            importer: None,
+        }
+    }
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let html = render("<script>alert(1)</script>");
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+
+        let html = render("a <b onclick=\"x\">bold</b> c");
+        assert!(!html.contains("<b"));
+        assert!(html.contains("&lt;b onclick="));
+    }
+
+    #[test]
+    fn unsafe_urls_are_stripped() {
+        for source in [
+            "[x](javascript:alert(1))",
+            "[x](JaVaScRiPt:alert(1))",
+            "[x](&#106;avascript:alert(1))",
+            "[x](&#x6A;AVASCRIPT&#58;alert(1))",
+            "[x](data:text/html;base64,AAAA)",
+        ] {
+            assert_eq!(render(source), "<p><a href=\"\">x</a></p>\n", "{}", source);
+        }
+        for source in [
+            "![alt](data:image/png;base64,AAAA)",
+            "![alt](JAVASCRIPT:alert(1))",
+            "![alt](&#106;avascript:alert(1))",
+        ] {
+            assert_eq!(render(source), "<p>alt</p>\n", "{}", source);
+        }
+        assert!(render("[x](https://example.com/a)").contains("href=\"https://example.com/a\""));
+    }
+
+    #[test]
+    fn forged_spoiler_marker_is_escaped() {
+        let html = render("<!--spoiler-0-open-0-->\n\nsecret\n\n<!--spoiler-0-close-->");
+        assert!(!html.contains("<details"));
+        assert!(html.contains("&lt;!--spoiler-0-open-0--&gt;"));
+
+        let html = render(":::spoiler 标题\nsecret\n:::");
+        assert!(html.starts_with("<details class=\"spoiler\"><summary>标题</summary>"));
+    }
+
+    #[test]
+    fn spoiler_in_code_stays_literal() {
+        let html = render("```\n:::spoiler\nx\n:::\n```");
+        assert_eq!(html, "<pre><code>:::spoiler\nx\n:::\n</code></pre>\n");
+    }
+
+    #[test]
+    fn ruby_outside_code_only() {
+        assert_eq!(
+            render("{漢字|かんじ}を読む"),
+            "<p><ruby>漢字<rp>(</rp><rt>かんじ</rt><rp>)</rp></ruby>を読む</p>\n"
+        );
+        let html = render("`{漢字|かんじ}`\n\n```\n{漢字|かんじ}\n```");
+        assert!(!html.contains("<ruby>"));
+        assert!(html.contains("<code>{漢字|かんじ}</code>"));
+    }
+
+    #[test]
+    fn embeds_resolve_or_drop() {
+        let mut embeds = HashMap::new();
+        embeds.insert("abc".to_owned(), local_file("abc", "image/abc.webp"));
+        let html = render_markdown("![图](local-file:abc) ![无](local-file:missing)", &embeds);
+        assert!(html.contains("<img src=\"") && html.contains("/image/abc.webp\" alt=\"图\""));
+        assert!(!html.contains("missing"));
+        assert!(html.contains(" 无</p>"));
+    }
+
+    #[test]
+    fn footnotes_render() {
+        let html = render("text[^1]\n\n[^1]: note");
+        assert!(html.contains("<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup>"));
+        assert!(html.contains("<div class=\"footnote-definition\" id=\"1\">"));
+    }
+}
@@ -1,4 +1,45 @@
 pub enum SiteContentKind {
     Unknown = 0,
     Novel = 1,
+    NovelExport = 2,
+}
+
+pub enum JobTrigger {
+    Schedule = 0,
+    Manual = 1,
+}
+
+pub enum JobRunStatus {
+    Running = 0,
+    Succeeded = 1,
+    Failed = 2,
+}
+
+pub enum TaskKind {
+    ImageUpload = 0,
+    ImageFromWeb = 1,
+    NovelCover = 2,
+}
+
+pub enum TaskStatus {
+    Pending = 0,
+    Running = 1,
+    Succeeded = 2,
+    Failed = 3,
+}
+
+pub enum TagNamespace {
+    General = 0,
+    Genre = 1,
+    Character = 2,
+    Language = 3,
+}
+
+pub enum AuthorPlatform {
+    Website = 0,
+    Pixiv = 1,
+    Twitter = 2,
+    Fanbox = 3,
+    Skeb = 4,
+    Danbooru = 5,
 }
@@ -1,5 +1,5 @@
 use crate::schema::*;
-use crate::utils::datetime_format;
+use crate::utils::{datetime_format, datetime_format_option};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -19,4 +19,77 @@ pub struct Novel {
     pub created_by: Option<i32>,
     #[serde(with = "datetime_format")]
     pub created_at: DateTime<Utc>,
+    pub cover_local_file_id: Option<String>,
+    pub author_id: Option<i32>,
+}
+
+#[derive(
+    Queryable,
+    Selectable,
+    Insertable,
+    Debug,
+    Clone,
+    Identifiable,
+    Associations,
+    Deserialize,
+    Serialize,
+)]
+#[diesel(belongs_to(Novel))]
+pub struct NovelChapter {
+    pub id: i32,
+    pub novel_id: i32,
+    pub position: i32,
+    pub title: String,
+    pub content: String,
+    pub word_count: i32,
+    #[serde(with = "datetime_format_option")]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "datetime_format")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone, Identifiable, Deserialize, Serialize)]
+#[diesel(table_name = series)]
+pub struct Series {
+    pub id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(
+    Queryable,
+    Selectable,
+    Insertable,
+    Debug,
+    Clone,
+    Identifiable,
+    Associations,
+    Deserialize,
+    Serialize,
+)]
+#[diesel(belongs_to(Series))]
+#[diesel(belongs_to(Novel))]
+pub struct SeriesNovel {
+    pub id: i32,
+    pub series_id: i32,
+    pub novel_id: i32,
+    pub position: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, Identifiable, Deserialize, Serialize)]
+pub struct Tag {
+    pub id: i32,
+    pub namespace: i16,
+    pub name: String,
+    /// 写入tags数组时使用的字符串，带有namespace前缀
+    pub key: String,
+    pub alias_of: Option<i32>,
+    pub novel_count: i32,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
+    pub image_count: i32,
 }
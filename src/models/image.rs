@@ -1,7 +1,7 @@
 // Generated by diesel_ext
 
 use crate::schema::*;
-use crate::utils::{datetime_format, naive_date_format};
+use crate::utils::{datetime_format, datetime_format_option, naive_date_format};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +12,35 @@ pub struct Author {
     pub id: i32,
     pub name: String,
     pub urls: Option<Vec<Option<String>>>,
+    pub avatar_local_file_id: Option<String>,
+    pub banner_local_file_id: Option<String>,
+}
+
+#[derive(
+    Queryable, Selectable, Debug, Clone, Identifiable, Associations, Deserialize, Serialize,
+)]
+#[diesel(belongs_to(Author))]
+#[diesel(table_name = author_aliases)]
+pub struct AuthorAlias {
+    pub id: i32,
+    pub author_id: i32,
+    pub name: String,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(
+    Queryable, Selectable, Debug, Clone, Identifiable, Associations, Deserialize, Serialize,
+)]
+#[diesel(belongs_to(Author))]
+pub struct AuthorLink {
+    pub id: i32,
+    pub author_id: i32,
+    pub platform: i16,
+    pub handle: String,
+    pub url: String,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(
@@ -33,6 +62,7 @@ pub struct ImageItem {
     pub date: NaiveDate,
     pub nsfw: bool,
     pub author_id: Option<i32>,
+    pub tags: Vec<Option<String>>,
 }
 
 #[derive(Queryable, Selectable, Insertable, Debug, Clone, Identifiable, Serialize, Deserialize)]
@@ -42,6 +72,15 @@ pub struct LocalFile {
     pub path: String,
     #[serde(with = "datetime_format")]
     pub created_at: DateTime<Utc>,
+    /// 从网络导入时请求的地址
+    pub source_url: Option<String>,
+    /// 重定向后的地址
+    pub source_final_url: Option<String>,
+    #[serde(with = "datetime_format_option", default)]
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub source_headers: Option<serde_json::Value>,
+    /// 导入器名称，直接下载的图片为None
+    pub importer: Option<String>,
 }
 
 #[derive(
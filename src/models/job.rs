@@ -0,0 +1,32 @@
+use crate::schema::*;
+use crate::utils::{datetime_format, datetime_format_option};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use chrono::{DateTime, Utc};
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone, Identifiable, Deserialize, Serialize)]
+pub struct JobRun {
+    pub id: i32,
+    pub job_name: String,
+    pub trigger: i16,
+    pub status: i16,
+    pub processed_count: i64,
+    pub affected_count: i64,
+    pub error: Option<String>,
+    #[serde(with = "datetime_format")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "datetime_format_option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone, Identifiable, Deserialize, Serialize)]
+#[diesel(primary_key(job_name))]
+pub struct JobLease {
+    pub job_name: String,
+    pub holder: String,
+    #[serde(with = "datetime_format")]
+    pub acquired_at: DateTime<Utc>,
+    #[serde(with = "datetime_format")]
+    pub expires_at: DateTime<Utc>,
+}
@@ -1,7 +1,11 @@
 mod content;
 mod image;
+mod job;
 mod storage;
+mod task;
 
 pub use content::*;
 pub use image::*;
+pub use job::*;
 pub use storage::*;
+pub use task::*;
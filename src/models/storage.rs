@@ -18,4 +18,5 @@ pub struct SiteStorage {
     pub created_by: Option<i32>,
     #[serde(with = "datetime_format")]
     pub created_at: DateTime<Utc>,
+    pub metadata: Option<serde_json::Value>,
 }
@@ -0,0 +1,37 @@
+use crate::schema::*;
+use crate::utils::{datetime_format, datetime_format_option};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use chrono::{DateTime, Utc};
+
+#[derive(
+    Queryable,
+    QueryableByName,
+    Selectable,
+    Insertable,
+    Debug,
+    Clone,
+    Identifiable,
+    Deserialize,
+    Serialize,
+)]
+pub struct Task {
+    pub id: i32,
+    pub kind: i16,
+    pub status: i16,
+    pub payload: serde_json::Value,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    #[serde(with = "datetime_format")]
+    pub run_after: DateTime<Utc>,
+    pub locked_by: Option<String>,
+    #[serde(with = "datetime_format_option")]
+    pub locked_at: Option<DateTime<Utc>>,
+    #[serde(with = "datetime_format")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "datetime_format_option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
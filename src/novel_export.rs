@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+};
+
+use chrono::Utc;
+use quick_xml::escape::escape;
+use serde::Serialize;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::markdown::{embed_ids, render_markdown_xhtml};
+
+/// 生成逻辑变化时递增，使已缓存的导出文件失效
+const EXPORT_VERSION: u32 = 3;
+
+/// 关联文件中没有语言信息时使用
+pub const DEFAULT_EXPORT_LANGUAGE: &str = "zh";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ExportChapter {
+    pub id: i32,
+    pub title: String,
+    /// Markdown正文
+    pub content: String,
+}
+
+/// 导出所需的全部内容，序列化结果用于计算缓存的hash
+#[derive(Serialize, Clone, Debug)]
+pub struct ExportBook {
+    /// 使内容相同的不同小说得到不同的hash，避免共用同一个缓存文件
+    pub novel_id: i32,
+    pub title: String,
+    pub author: String,
+    pub description: Option<String>,
+    pub language: String,
+    pub cover_local_file_id: Option<String>,
+    pub chapters: Vec<ExportChapter>,
+}
+
+impl ExportBook {
+    pub fn content_hash(&self) -> String {
+        let serialized = serde_json::to_vec(&(EXPORT_VERSION, self)).unwrap_or_default();
+        format!("{:x}", md5::compute(serialized))
+    }
+
+    /// 章节中引用的local_files id
+    pub fn embed_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+
+        for chapter in &self.chapters {
+            for id in embed_ids(&chapter.content) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        ids
+    }
+}
+
+/// 打包进电子书的嵌入图片，data为WebP格式
+pub struct ExportEmbed {
+    pub local_file_id: String,
+    pub data: Vec<u8>,
+}
+
+fn embed_file_name(index: usize) -> String {
+    format!("images/image-{:04}.webp", index + 1)
+}
+
+fn chapter_file_name(index: usize) -> String {
+    format!("chapter-{:04}.xhtml", index + 1)
+}
+
+fn xhtml_document(title: &str, language: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}" lang="{language}">
+<head>
+<meta charset="UTF-8"/>
+<title>{title}</title>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        title = escape(title),
+        language = escape(language),
+        body = body,
+    )
+}
+
+fn build_opf(book: &ExportBook, identifier: &str, has_cover: bool, embed_count: usize) -> String {
+    let mut manifest = String::from(
+        r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+"#,
+    );
+    let mut spine = String::new();
+
+    if has_cover {
+        manifest.push_str(
+            r#"    <item id="cover-image" href="cover.webp" media-type="image/webp" properties="cover-image"/>
+    <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+"#,
+        );
+        spine.push_str("    <itemref idref=\"cover\" linear=\"no\"/>\n");
+    }
+
+    for index in 0..book.chapters.len() {
+        manifest.push_str(&format!(
+            "    <item id=\"chapter-{index}\" href=\"{href}\" media-type=\"application/xhtml+xml\"/>\n",
+            index = index + 1,
+            href = chapter_file_name(index),
+        ));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", index + 1));
+    }
+
+    for index in 0..embed_count {
+        manifest.push_str(&format!(
+            "    <item id=\"image-{index}\" href=\"{href}\" media-type=\"image/webp\"/>\n",
+            index = index + 1,
+            href = embed_file_name(index),
+        ));
+    }
+
+    let description = book
+        .description
+        .as_deref()
+        .map(|v| format!("    <dc:description>{}</dc:description>\n", escape(v)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:creator>{author}</dc:creator>
+    <dc:language>{language}</dc:language>
+{description}    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        identifier = escape(identifier),
+        title = escape(&book.title),
+        author = escape(&book.author),
+        language = escape(&book.language),
+        description = description,
+        modified = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+        manifest = manifest,
+        spine = spine,
+    )
+}
+
+fn build_nav(book: &ExportBook) -> String {
+    let items = book
+        .chapters
+        .iter()
+        .enumerate()
+        .map(|(index, chapter)| {
+            format!(
+                "<li><a href=\"{}\">{}</a></li>",
+                chapter_file_name(index),
+                escape(&chapter.title)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    xhtml_document(
+        &book.title,
+        &book.language,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<ol>\n{}\n</ol>\n</nav>",
+            items
+        ),
+    )
+}
+
+/// 生成EPUB 3文件，cover为WebP格式的封面
+///
+/// 章节中引用的local_files图片从embeds打包进文件，未提供的图片只保留说明文字
+pub fn build_epub(
+    book: &ExportBook,
+    identifier: &str,
+    cover: Option<&[u8]>,
+    embeds: &[ExportEmbed],
+) -> anyhow::Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // mimetype必须是第一个且不压缩的文件
+    writer.start_file("mimetype", stored)?;
+    writer.write_all(b"application/epub+zip")?;
+
+    writer.start_file("META-INF/container.xml", deflated)?;
+    writer.write_all(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#,
+    )?;
+
+    writer.start_file("OEBPS/content.opf", deflated)?;
+    writer.write_all(build_opf(book, identifier, cover.is_some(), embeds.len()).as_bytes())?;
+
+    writer.start_file("OEBPS/nav.xhtml", deflated)?;
+    writer.write_all(build_nav(book).as_bytes())?;
+
+    if let Some(cover) = cover {
+        writer.start_file("OEBPS/cover.webp", stored)?;
+        writer.write_all(cover)?;
+
+        writer.start_file("OEBPS/cover.xhtml", deflated)?;
+        writer.write_all(
+            xhtml_document(
+                &book.title,
+                &book.language,
+                &format!("<img src=\"cover.webp\" alt=\"{}\"/>", escape(&book.title)),
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    let mut embed_hrefs = HashMap::new();
+    for (index, embed) in embeds.iter().enumerate() {
+        writer.start_file(format!("OEBPS/{}", embed_file_name(index)), stored)?;
+        writer.write_all(&embed.data)?;
+        embed_hrefs.insert(embed.local_file_id.to_owned(), embed_file_name(index));
+    }
+
+    for (index, chapter) in book.chapters.iter().enumerate() {
+        let body = format!(
+            "<h1>{}</h1>\n{}",
+            escape(&chapter.title),
+            render_markdown_xhtml(&chapter.content, &embed_hrefs)
+        );
+
+        writer.start_file(format!("OEBPS/{}", chapter_file_name(index)), deflated)?;
+        writer.write_all(xhtml_document(&chapter.title, &book.language, &body).as_bytes())?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use rocket::http::{ContentType, Status};
+
+const PDF_MAGIC: &[u8] = b"%PDF-";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// EPUB要求第一个zip条目为未压缩的mimetype文件，其内容从偏移30+8处开始
+const EPUB_MIMETYPE_ENTRY: &[u8] = b"mimetypeapplication/epub+zip";
+const EPUB_MIMETYPE_OFFSET: usize = 30;
+/// 换行、制表符以外的控制字符超过该比例时视为二进制文件
+const MAX_CONTROL_CHAR_RATIO: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NovelFormat {
+    Pdf,
+    Epub,
+    Text,
+    Markdown,
+}
+
+impl NovelFormat {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            NovelFormat::Pdf => ContentType::PDF,
+            NovelFormat::Epub => ContentType::EPUB,
+            NovelFormat::Text => ContentType::Plain,
+            NovelFormat::Markdown => ContentType::Markdown,
+        }
+    }
+
+    /// 根据site_storage.mime_type还原格式
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        let content_type = ContentType::parse_flexible(mime_type)?;
+
+        [
+            NovelFormat::Pdf,
+            NovelFormat::Epub,
+            NovelFormat::Text,
+            NovelFormat::Markdown,
+        ]
+        .into_iter()
+        .find(|v| {
+            let known = v.content_type();
+            known.top() == content_type.top() && known.sub() == content_type.sub()
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            NovelFormat::Pdf => "pdf",
+            NovelFormat::Epub => "epub",
+            NovelFormat::Text => "txt",
+            NovelFormat::Markdown => "md",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NovelFormatError {
+    /// 不是受支持的格式
+    Unsupported,
+    /// 文本文件无法以检测到的编码解码
+    UndecodableText(&'static Encoding),
+}
+
+impl fmt::Display for NovelFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NovelFormatError::Unsupported => write!(f, "unsupported novel format"),
+            NovelFormatError::UndecodableText(encoding) => {
+                write!(f, "text is not valid {}", encoding.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for NovelFormatError {}
+
+pub fn novel_format_error_to_status(_: NovelFormatError) -> Status {
+    Status::UnprocessableEntity
+}
+
+/// 检测完成的小说文件，文本格式已统一转换为UTF-8
+#[derive(Debug)]
+pub struct DetectedNovel {
+    pub format: NovelFormat,
+    pub data: Vec<u8>,
+}
+
+fn is_epub(data: &[u8]) -> bool {
+    data.starts_with(ZIP_MAGIC)
+        && data
+            .get(EPUB_MIMETYPE_OFFSET..EPUB_MIMETYPE_OFFSET + EPUB_MIMETYPE_ENTRY.len())
+            .is_some_and(|v| v == EPUB_MIMETYPE_ENTRY)
+}
+
+fn is_binary_text(text: &str) -> bool {
+    let mut total = 0usize;
+    let mut control = 0usize;
+
+    for c in text.chars() {
+        total += 1;
+        if c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0c') {
+            control += 1;
+        }
+    }
+
+    control as f64 > total as f64 * MAX_CONTROL_CHAR_RATIO
+}
+
+/// 检测文本编码并转换为UTF-8，包含NUL字符或大量控制字符的数据视为二进制文件
+fn decode_text(data: &[u8]) -> Result<String, NovelFormatError> {
+    let (encoding, bom_length) = match Encoding::for_bom(data) {
+        Some(v) => v,
+        None if std::str::from_utf8(data).is_ok() => (UTF_8, 0),
+        None => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(data, true);
+            (detector.guess(None, true), 0)
+        }
+    };
+
+    let data = &data[bom_length..];
+
+    if data.contains(&0) && encoding.is_ascii_compatible() {
+        return Err(NovelFormatError::Unsupported);
+    }
+
+    let text = encoding
+        .decode_without_bom_handling_and_without_replacement(data)
+        .map(|v| v.into_owned())
+        .ok_or(NovelFormatError::UndecodableText(encoding))?;
+
+    if is_binary_text(&text) {
+        return Err(NovelFormatError::Unsupported);
+    }
+
+    Ok(text)
+}
+
+/// 根据文件内容判断格式，声明的类型和文件名只用于区分纯文本和Markdown
+pub fn detect_novel_format(
+    data: Vec<u8>,
+    declared_type: Option<&ContentType>,
+    file_name: Option<&str>,
+) -> Result<DetectedNovel, NovelFormatError> {
+    if data.starts_with(PDF_MAGIC) {
+        return Ok(DetectedNovel {
+            format: NovelFormat::Pdf,
+            data,
+        });
+    }
+
+    if is_epub(&data) {
+        return Ok(DetectedNovel {
+            format: NovelFormat::Epub,
+            data,
+        });
+    }
+
+    if data.starts_with(ZIP_MAGIC) || data.is_empty() {
+        return Err(NovelFormatError::Unsupported);
+    }
+
+    let text = decode_text(&data)?;
+
+    let is_markdown = declared_type.is_some_and(|v| v.is_markdown())
+        || file_name
+            .and_then(|v| v.rsplit_once('.'))
+            .is_some_and(|(_, ext)| {
+                ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown")
+            });
+
+    Ok(DetectedNovel {
+        format: if is_markdown {
+            NovelFormat::Markdown
+        } else {
+            NovelFormat::Text
+        },
+        data: text.into_bytes(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{GBK, SHIFT_JIS};
+
+    use super::*;
+
+    const CHINESE: &str = "第一章 重逢\n\n那天傍晚，雨下得很大。她撑着伞站在车站门口，看着来来往往的行人，心里想着很久以前的事情。\n";
+    const JAPANESE: &str = "第一章 再会\n\nその日の夕方は、雨がひどく降っていた。彼女は傘を差して駅の入口に立ち、行き交う人々を眺めながら、昔のことを思い出していた。\n";
+
+    fn detect(data: &[u8], file_name: Option<&str>) -> Result<DetectedNovel, NovelFormatError> {
+        detect_novel_format(data.to_vec(), None, file_name)
+    }
+
+    fn detect_text(data: &[u8]) -> String {
+        let detected = detect(data, None).unwrap();
+        assert_eq!(detected.format, NovelFormat::Text);
+        String::from_utf8(detected.data).unwrap()
+    }
+
+    #[test]
+    fn magic_bytes() {
+        let pdf = detect(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n", None).unwrap();
+        assert_eq!(pdf.format, NovelFormat::Pdf);
+
+        let mut epub = ZIP_MAGIC.to_vec();
+        epub.resize(EPUB_MIMETYPE_OFFSET, 0);
+        epub.extend_from_slice(EPUB_MIMETYPE_ENTRY);
+        let epub = detect(&epub, Some("a.txt")).unwrap();
+        assert_eq!(epub.format, NovelFormat::Epub);
+
+        let mut zip = ZIP_MAGIC.to_vec();
+        zip.resize(EPUB_MIMETYPE_OFFSET, 0);
+        zip.extend_from_slice(b"chapter1.txthello");
+        assert!(matches!(
+            detect(&zip, Some("a.epub")),
+            Err(NovelFormatError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn legacy_encodings() {
+        let (gbk, _, _) = GBK.encode(CHINESE);
+        assert_eq!(detect_text(&gbk), CHINESE);
+
+        let (shift_jis, _, _) = SHIFT_JIS.encode(JAPANESE);
+        assert_eq!(detect_text(&shift_jis), JAPANESE);
+    }
+
+    #[test]
+    fn utf8_with_and_without_bom() {
+        assert_eq!(detect_text(CHINESE.as_bytes()), CHINESE);
+
+        let mut with_bom = b"\xef\xbb\xbf".to_vec();
+        with_bom.extend_from_slice(CHINESE.as_bytes());
+        assert_eq!(detect_text(&with_bom), CHINESE);
+    }
+
+    #[test]
+    fn markdown_detection() {
+        let by_name = detect(b"# title\n\ntext", Some("novel.MD")).unwrap();
+        assert_eq!(by_name.format, NovelFormat::Markdown);
+
+        let by_type =
+            detect_novel_format(b"# title".to_vec(), Some(&ContentType::Markdown), None).unwrap();
+        assert_eq!(by_type.format, NovelFormat::Markdown);
+
+        let plain = detect(b"# title", Some("novel.txt")).unwrap();
+        assert_eq!(plain.format, NovelFormat::Text);
+    }
+
+    #[test]
+    fn binary_rejection() {
+        assert!(matches!(
+            detect(b"text\0with nul", None),
+            Err(NovelFormatError::Unsupported)
+        ));
+
+        let binary: Vec<u8> = (0..4096u32)
+            .map(|i| (i * 7 % 256) as u8)
+            .filter(|v| *v != 0)
+            .collect();
+        assert!(matches!(
+            detect(&binary, None),
+            Err(NovelFormatError::Unsupported)
+        ));
+
+        let control = format!("{}\x01\x02\x03\x1b", "text ".repeat(10));
+        assert!(matches!(
+            detect(control.as_bytes(), None),
+            Err(NovelFormatError::Unsupported)
+        ));
+
+        assert!(matches!(
+            detect(b"", None),
+            Err(NovelFormatError::Unsupported)
+        ));
+    }
+}
@@ -0,0 +1,562 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use anyhow::{anyhow, Context};
+use lopdf::{Dictionary, Document, Object};
+use quick_xml::{events::Event, Reader};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::novel_format::NovelFormat;
+
+/// 从小说文件中提取的元数据，保存在site_storage.metadata中
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct NovelMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub language: Option<String>,
+    pub page_count: Option<i64>,
+    pub word_count: Option<i64>,
+    pub toc: Vec<TocEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TocEntry {
+    /// 目录层级，从1开始
+    pub level: u32,
+    pub title: String,
+    pub page: Option<u32>,
+}
+
+pub fn extract_novel_metadata(format: NovelFormat, data: &[u8]) -> anyhow::Result<NovelMetadata> {
+    match format {
+        NovelFormat::Pdf => extract_pdf(data),
+        NovelFormat::Epub => extract_epub(data),
+        NovelFormat::Text => Ok(extract_text(std::str::from_utf8(data)?, false)),
+        NovelFormat::Markdown => Ok(extract_text(std::str::from_utf8(data)?, true)),
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{20000}'..='\u{2FFFF}')
+}
+
+/// 中日韩文字每个字计为一词，其他语言以连续的字母数字计为一词
+pub fn count_words(text: &str) -> i64 {
+    let mut count = 0;
+    let mut in_word = false;
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                count += 1;
+                in_word = true;
+            }
+        } else {
+            in_word = false;
+        }
+    }
+
+    count
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_owned())
+    }
+}
+
+fn extract_text(text: &str, markdown: bool) -> NovelMetadata {
+    let mut metadata = NovelMetadata {
+        word_count: Some(count_words(text)),
+        ..Default::default()
+    };
+
+    if markdown {
+        for line in text.lines() {
+            let level = line.chars().take_while(|c| *c == '#').count();
+            if !(1..=6).contains(&level) || !line[level..].starts_with(' ') {
+                continue;
+            }
+
+            let Some(title) = non_empty(line[level..].trim_end_matches('#').to_owned()) else {
+                continue;
+            };
+
+            if level == 1 && metadata.title.is_none() {
+                metadata.title = Some(title.to_owned());
+            }
+
+            metadata.toc.push(TocEntry {
+                level: level as u32,
+                title,
+                page: None,
+            });
+        }
+    }
+
+    metadata
+}
+
+/// PDF文本字符串为带BOM的UTF-16BE或PDFDocEncoding
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units = rest
+            .chunks_exact(2)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]))
+            .collect::<Vec<u16>>();
+        String::from_utf16_lossy(&units)
+    } else {
+        bytes.iter().map(|b| *b as char).collect()
+    }
+}
+
+fn extract_pdf(data: &[u8]) -> anyhow::Result<NovelMetadata> {
+    let document = Document::load_mem(data).context("failed to parse pdf")?;
+
+    let info = document
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|v| document.dereference(v).ok())
+        .and_then(|(_, v)| v.as_dict().ok());
+
+    let info_string = |key: &[u8]| {
+        info.and_then(|v| v.get(key).ok())
+            .and_then(|v| document.dereference(v).ok())
+            .and_then(|(_, v)| v.as_str().ok())
+            .and_then(|v| non_empty(decode_pdf_string(v)))
+    };
+
+    let language = document
+        .catalog()
+        .ok()
+        .and_then(|v| v.get(b"Lang").ok())
+        .and_then(|v| match v {
+            Object::String(bytes, _) => non_empty(decode_pdf_string(bytes)),
+            _ => None,
+        });
+
+    // 目录损坏时不影响其他元数据
+    let toc = document
+        .get_toc()
+        .map(|v| {
+            v.toc
+                .into_iter()
+                .map(|v| TocEntry {
+                    level: v.level as u32,
+                    title: v.title,
+                    page: Some(v.page as u32),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(NovelMetadata {
+        title: info_string(b"Title"),
+        author: info_string(b"Author"),
+        language,
+        page_count: Some(document.get_pages().len() as i64),
+        word_count: None,
+        toc,
+    })
+}
+
+fn read_zip_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> anyhow::Result<String> {
+    let mut content = String::new();
+    archive
+        .by_name(path)
+        .with_context(|| format!("missing {}", path))?
+        .read_to_string(&mut content)
+        .with_context(|| format!("failed to read {}", path))?;
+    Ok(content)
+}
+
+/// 将相对于base所在目录的路径转换为压缩包中的路径
+fn resolve_path(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut parts = base.split('/').collect::<Vec<&str>>();
+    parts.pop();
+
+    for part in href.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+fn find_attribute(
+    e: &quick_xml::events::BytesStart,
+    name: &[u8],
+) -> anyhow::Result<Option<String>> {
+    for attr in e.attributes() {
+        let attr = attr?;
+        if attr.key.local_name().as_ref() == name {
+            return Ok(Some(attr.unescape_value()?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+struct ManifestItem {
+    href: String,
+    properties: Option<String>,
+}
+
+struct OpfPackage {
+    metadata: NovelMetadata,
+    manifest: HashMap<String, ManifestItem>,
+    spine: Vec<String>,
+    ncx_id: Option<String>,
+    /// EPUB2中以<meta name="cover">指定的封面
+    cover_id: Option<String>,
+}
+
+fn parse_opf(content: &str) -> anyhow::Result<OpfPackage> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut package = OpfPackage {
+        metadata: NovelMetadata::default(),
+        manifest: HashMap::new(),
+        spine: Vec::new(),
+        ncx_id: None,
+        cover_id: None,
+    };
+    let mut current: Option<Vec<u8>> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                name @ (b"title" | b"creator" | b"language") => current = Some(name.to_vec()),
+                b"item" => {
+                    if let (Some(id), Some(href)) =
+                        (find_attribute(&e, b"id")?, find_attribute(&e, b"href")?)
+                    {
+                        let properties = find_attribute(&e, b"properties")?;
+                        package
+                            .manifest
+                            .insert(id, ManifestItem { href, properties });
+                    }
+                }
+                b"meta" if find_attribute(&e, b"name")?.as_deref() == Some("cover") => {
+                    package.cover_id = find_attribute(&e, b"content")?
+                }
+                b"spine" => package.ncx_id = find_attribute(&e, b"toc")?,
+                b"itemref" => {
+                    if let Some(idref) = find_attribute(&e, b"idref")? {
+                        package.spine.push(idref);
+                    }
+                }
+                _ => {}
+            },
+            Event::Text(t) => {
+                let text = non_empty(t.unescape()?.into_owned());
+                let metadata = &mut package.metadata;
+                match current.as_deref() {
+                    Some(b"title") if metadata.title.is_none() => metadata.title = text,
+                    Some(b"creator") if metadata.author.is_none() => metadata.author = text,
+                    Some(b"language") if metadata.language.is_none() => metadata.language = text,
+                    _ => {}
+                }
+            }
+            Event::End(_) => current = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(package)
+}
+
+/// 解析EPUB3导航文档中的toc
+fn parse_nav(content: &str) -> anyhow::Result<Vec<TocEntry>> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut toc = Vec::new();
+    let mut in_toc = false;
+    let mut depth = 0;
+    let mut title: Option<String> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"nav" => in_toc = find_attribute(&e, b"type")?.as_deref() == Some("toc"),
+                b"ol" if in_toc => depth += 1,
+                b"a" | b"span" if in_toc => title = Some(String::new()),
+                _ => {}
+            },
+            Event::Text(t) => {
+                if let Some(title) = title.as_mut() {
+                    title.push_str(&t.unescape()?);
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"nav" => in_toc = false,
+                b"ol" if in_toc => depth -= 1,
+                b"a" | b"span" if in_toc => {
+                    if let Some(title) = title.take().and_then(non_empty) {
+                        toc.push(TocEntry {
+                            level: depth,
+                            title,
+                            page: None,
+                        });
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(toc)
+}
+
+/// 解析EPUB2的toc.ncx
+fn parse_ncx(content: &str) -> anyhow::Result<Vec<TocEntry>> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut toc = Vec::new();
+    let mut depth = 0;
+    let mut in_label = false;
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"navPoint" => depth += 1,
+                b"navLabel" => in_label = depth > 0,
+                b"text" => in_text = in_label,
+                _ => {}
+            },
+            Event::Text(t) if in_text => {
+                if let Some(title) = non_empty(t.unescape()?.into_owned()) {
+                    toc.push(TocEntry {
+                        level: depth,
+                        title,
+                        page: None,
+                    });
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"navPoint" => depth -= 1,
+                b"navLabel" => in_label = false,
+                b"text" => in_text = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(toc)
+}
+
+fn count_xhtml_words(content: &str) -> anyhow::Result<i64> {
+    let mut reader = Reader::from_str(content);
+    let mut count = 0;
+    let mut in_body = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"body" => in_body = true,
+            Event::End(e) if e.local_name().as_ref() == b"body" => in_body = false,
+            Event::Text(t) if in_body => count += count_words(&t.unescape()?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(count)
+}
+
+/// 读取container.xml中指定的OPF文件路径
+fn find_opf_path(archive: &mut ZipArchive<Cursor<&[u8]>>) -> anyhow::Result<String> {
+    let container = read_zip_entry(archive, "META-INF/container.xml")?;
+    let mut reader = Reader::from_str(&container);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = find_attribute(&e, b"full-path")? {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => return Err(anyhow!("missing rootfile in container.xml")),
+            _ => {}
+        }
+    }
+}
+
+fn extract_epub(data: &[u8]) -> anyhow::Result<NovelMetadata> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).context("failed to open epub")?;
+
+    let opf_path = find_opf_path(&mut archive)?;
+    let package = parse_opf(&read_zip_entry(&mut archive, &opf_path)?)?;
+    let mut metadata = package.metadata;
+
+    let nav = package.manifest.values().find(|v| {
+        v.properties
+            .as_deref()
+            .is_some_and(|v| v.split_whitespace().any(|v| v == "nav"))
+    });
+
+    let toc = if let Some(nav) = nav {
+        parse_nav(&read_zip_entry(
+            &mut archive,
+            &resolve_path(&opf_path, &nav.href),
+        )?)
+    } else if let Some(ncx) = package.ncx_id.and_then(|v| package.manifest.get(&v)) {
+        parse_ncx(&read_zip_entry(
+            &mut archive,
+            &resolve_path(&opf_path, &ncx.href),
+        )?)
+    } else {
+        Ok(Vec::new())
+    };
+    metadata.toc = toc.unwrap_or_default();
+
+    let mut word_count = 0;
+    for idref in &package.spine {
+        let Some(item) = package.manifest.get(idref) else {
+            continue;
+        };
+        let path = resolve_path(&opf_path, &item.href);
+        word_count += read_zip_entry(&mut archive, &path)
+            .and_then(|v| count_xhtml_words(&v))
+            .unwrap_or_default();
+    }
+    metadata.word_count = Some(word_count);
+
+    Ok(metadata)
+}
+
+/// 提取小说的封面图片，返回未经转换的原始图片数据
+pub fn extract_novel_cover(format: NovelFormat, data: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    match format {
+        NovelFormat::Pdf => extract_pdf_cover(data),
+        NovelFormat::Epub => extract_epub_cover(data),
+        NovelFormat::Text | NovelFormat::Markdown => Ok(None),
+    }
+}
+
+/// 以第一页中嵌入的最大JPEG(DCTDecode)图片作为封面
+///
+/// 没有PDF渲染器，不会把页面渲染成图片：第一页只有文字、矢量图形或
+/// 其他编码的图片(JPX、Flate等)时返回None，第一页的文字也不会出现在封面上
+fn extract_pdf_cover(data: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    let document = Document::load_mem(data).context("failed to parse pdf")?;
+
+    let Some(page_id) = document.get_pages().values().next().copied() else {
+        return Ok(None);
+    };
+
+    let (inline_resources, resource_ids) = document.get_page_resources(page_id);
+    let resources = inline_resources
+        .into_iter()
+        .chain(
+            resource_ids
+                .into_iter()
+                .filter_map(|id| document.get_dictionary(id).ok()),
+        )
+        .collect::<Vec<&Dictionary>>();
+
+    let mut cover: Option<(i64, &[u8])> = None;
+
+    for resource in resources {
+        let Ok(xobjects) = resource
+            .get_deref(b"XObject", &document)
+            .and_then(|v| v.as_dict())
+        else {
+            continue;
+        };
+
+        for (_, xobject) in xobjects.iter() {
+            let Ok(stream) = document
+                .dereference(xobject)
+                .and_then(|(_, v)| v.as_stream())
+            else {
+                continue;
+            };
+
+            let is_image = stream
+                .dict
+                .get(b"Subtype")
+                .and_then(|v| v.as_name())
+                .is_ok_and(|v| v == b"Image");
+            let is_jpeg = match stream.dict.get(b"Filter") {
+                Ok(Object::Name(name)) => name == b"DCTDecode",
+                Ok(Object::Array(filters)) => {
+                    filters.len() == 1 && filters[0].as_name().is_ok_and(|v| v == b"DCTDecode")
+                }
+                _ => false,
+            };
+
+            if !is_image || !is_jpeg {
+                continue;
+            }
+
+            let dimension = |key: &[u8]| stream.dict.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+            let area = dimension(b"Width") * dimension(b"Height");
+
+            if cover.is_none_or(|(v, _)| area > v) {
+                cover = Some((area, &stream.content));
+            }
+        }
+    }
+
+    Ok(cover.map(|(_, v)| v.to_vec()))
+}
+
+fn extract_epub_cover(data: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).context("failed to open epub")?;
+
+    let opf_path = find_opf_path(&mut archive)?;
+    let package = parse_opf(&read_zip_entry(&mut archive, &opf_path)?)?;
+
+    let cover = package
+        .manifest
+        .values()
+        .find(|v| {
+            v.properties
+                .as_deref()
+                .is_some_and(|v| v.split_whitespace().any(|v| v == "cover-image"))
+        })
+        .or_else(|| package.cover_id.and_then(|v| package.manifest.get(&v)));
+
+    let Some(cover) = cover else {
+        return Ok(None);
+    };
+
+    let path = resolve_path(&opf_path, &cover.href);
+    let mut content = Vec::new();
+    archive
+        .by_name(&path)
+        .with_context(|| format!("missing {}", path))?
+        .read_to_end(&mut content)
+        .with_context(|| format!("failed to read {}", path))?;
+
+    Ok(Some(content))
+}
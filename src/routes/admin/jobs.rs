@@ -0,0 +1,124 @@
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use log::info;
+use rocket::{get, http::Status, post, serde::json::Json, Route, State};
+use serde::Serialize;
+
+use crate::{
+    db,
+    misc::enums::JobTrigger,
+    models::*,
+    schedule_jobs::{lease, JobError, JobRegistry},
+    schema,
+    utils::{require_admin, response::*, ApiTokenClaims, Pagination},
+};
+
+#[derive(Serialize)]
+struct JobInfo {
+    name: &'static str,
+    description: &'static str,
+    schedule: String,
+    running: bool,
+    lease: Option<JobLease>,
+    last_run: Option<JobRun>,
+}
+
+#[get("/")]
+async fn list_jobs(
+    db: &State<db::Pool>,
+    registry: &State<JobRegistry>,
+    auth: Option<ApiTokenClaims>,
+) -> Result<Json<ListResponse<JobInfo>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let mut results = Vec::with_capacity(registry.jobs().len());
+    for job in registry.jobs() {
+        let last_run = schema::job_runs::table
+            .filter(schema::job_runs::job_name.eq(job.name))
+            .order(schema::job_runs::started_at.desc())
+            .first::<JobRun>(&mut conn)
+            .await
+            .map(Some)
+            .or_else(|err| match err {
+                diesel::result::Error::NotFound => Ok(None),
+                _ => Err(Status::InternalServerError),
+            })?;
+
+        let lease = lease::current(&mut conn, job.name)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        results.push(JobInfo {
+            name: job.name,
+            description: job.description,
+            schedule: job.schedule.to_string(),
+            running: registry.is_running(job.name) || lease.is_some(),
+            lease,
+            last_run,
+        });
+    }
+
+    let count = results.len() as i64;
+
+    Ok(Json(ListResponse::new(results).count(count)))
+}
+
+#[get("/<name>/runs?<pg..>")]
+async fn list_job_runs(
+    db: &State<db::Pool>,
+    registry: &State<JobRegistry>,
+    auth: Option<ApiTokenClaims>,
+    name: &str,
+    pg: Pagination,
+) -> Result<Json<ListResponse<JobRun>>, Status> {
+    require_admin(auth)?;
+    registry.find(name).ok_or(Status::NotFound)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let runs = schema::job_runs::table
+        .filter(schema::job_runs::job_name.eq(name))
+        .order(schema::job_runs::started_at.desc())
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<JobRun>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = schema::job_runs::table
+        .filter(schema::job_runs::job_name.eq(name))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(ListResponse::new(runs).count(count)))
+}
+
+#[post("/<name>/run")]
+async fn trigger_job(
+    registry: &State<JobRegistry>,
+    auth: Option<ApiTokenClaims>,
+    name: &str,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    require_admin(auth)?;
+
+    let run_id = registry
+        .start(name, JobTrigger::Manual)
+        .await
+        .map_err(|err| match err {
+            JobError::NotFound => Status::NotFound,
+            JobError::AlreadyRunning
+            | JobError::LeasedElsewhere
+            | JobError::AlreadyScheduled => Status::Conflict,
+            _ => Status::InternalServerError,
+        })?;
+
+    info!("Job triggered manually: {} (run {})", name, run_id);
+
+    Ok(Json(InsertResponse { id: run_id }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list_jobs, list_job_runs, trigger_job]
+}
@@ -1,21 +1,85 @@
 use crate::{
+    author_links::{self, find_author_by_link, load_author_links, parse_link, sync_author_links},
     db,
+    misc::enums::AuthorPlatform,
     models::*,
+    routes::tags::TagsCount,
     schema,
     utils::{
-        parse_order_from_string, response::*, result_error_to_status, ApiTokenClaims, Pagination,
+        naive_date_format, naive_date_format_option, parse_order_from_string, require_admin,
+        response::*, result_error_to_status, ApiTokenClaims, Pagination,
     },
 };
 use diesel_order_with_direction::OrderWithDirectionDsl;
 use log::info;
 use rocket::{delete, get, http::Status, post, put, serde::json::Json, Route, State};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use chrono::NaiveDate;
 use diesel::{
-    delete, insert_into, prelude::Insertable, query_builder::AsChangeset, update,
-    ExpressionMethods, QueryDsl, TextExpressionMethods,
+    delete, insert_into,
+    prelude::Insertable,
+    query_builder::AsChangeset,
+    result::DatabaseErrorKind,
+    sql_query,
+    sql_types::{BigInt, Date, Integer, Nullable},
+    update, BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, QueryableByName,
+    TextExpressionMethods,
 };
-use diesel_async::RunQueryDsl;
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use std::collections::HashMap;
+
+/// 带有头像和横幅文件的作者
+#[derive(Serialize, Debug, Clone)]
+pub struct AuthorFull {
+    #[serde(flatten)]
+    pub author: Author,
+    pub avatar: Option<LocalFile>,
+    pub banner: Option<LocalFile>,
+}
+
+/// 批量读取作者的头像和横幅，结果与authors的顺序一致
+pub async fn load_authors_full(
+    conn: &mut AsyncPgConnection,
+    authors: Vec<Author>,
+) -> Result<Vec<AuthorFull>, diesel::result::Error> {
+    let file_ids = authors
+        .iter()
+        .flat_map(|v| [&v.avatar_local_file_id, &v.banner_local_file_id])
+        .flatten()
+        .collect::<Vec<&String>>();
+
+    let files = if file_ids.is_empty() {
+        HashMap::new()
+    } else {
+        schema::local_files::table
+            .filter(schema::local_files::id.eq_any(file_ids))
+            .load::<LocalFile>(conn)
+            .await?
+            .into_iter()
+            .map(|v| (v.id.to_owned(), v))
+            .collect::<HashMap<String, LocalFile>>()
+    };
+
+    Ok(authors
+        .into_iter()
+        .map(|author| AuthorFull {
+            avatar: author
+                .avatar_local_file_id
+                .as_ref()
+                .and_then(|v| files.get(v))
+                .cloned(),
+            banner: author
+                .banner_local_file_id
+                .as_ref()
+                .and_then(|v| files.get(v))
+                .cloned(),
+            author,
+        })
+        .collect())
+}
 
 #[get("/item?<id>&<name>&<pg..>")]
 async fn list_authors(
@@ -23,7 +87,7 @@ async fn list_authors(
     id: Option<i32>,
     name: Option<String>,
     pg: Pagination,
-) -> Result<Json<ListResponse<Author>>, Status> {
+) -> Result<Json<ListResponse<AuthorFull>>, Status> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
     let mut query = schema::authors::table.into_boxed();
@@ -35,10 +99,24 @@ async fn list_authors(
         query_count = query_count.filter(schema::authors::id.eq(val));
     }
 
-    // 以name筛选
+    // 以name筛选，同时匹配别名
     if let Some(name) = name {
-        query = query.filter(schema::authors::name.like(name.to_owned()));
-        query_count = query_count.filter(schema::authors::name.like(name.to_owned()));
+        let alias_author_ids = |name: String| {
+            schema::author_aliases::table
+                .filter(schema::author_aliases::name.like(name))
+                .select(schema::author_aliases::author_id)
+        };
+
+        query = query.filter(
+            schema::authors::name
+                .like(name.to_owned())
+                .or(schema::authors::id.eq_any(alias_author_ids(name.to_owned()))),
+        );
+        query_count = query_count.filter(
+            schema::authors::name
+                .like(name.to_owned())
+                .or(schema::authors::id.eq_any(alias_author_ids(name.to_owned()))),
+        );
     };
 
     // 顺序选择
@@ -59,6 +137,10 @@ async fn list_authors(
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    let authors = load_authors_full(&mut conn, authors)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
     let count = query_count
         .count()
         .get_result(&mut conn)
@@ -69,7 +151,7 @@ async fn list_authors(
 }
 
 #[get("/all")]
-async fn all_author(db: &State<db::Pool>) -> Result<Json<ListResponse<Author>>, Status> {
+async fn all_author(db: &State<db::Pool>) -> Result<Json<ListResponse<AuthorFull>>, Status> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
     let authors = schema::authors::table
@@ -77,6 +159,10 @@ async fn all_author(db: &State<db::Pool>) -> Result<Json<ListResponse<Author>>,
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    let authors = load_authors_full(&mut conn, authors)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
     let count = schema::authors::table
         .count()
         .get_result(&mut conn)
@@ -87,7 +173,7 @@ async fn all_author(db: &State<db::Pool>) -> Result<Json<ListResponse<Author>>,
 }
 
 #[get("/item/<id>")]
-async fn get_author(db: &State<db::Pool>, id: i32) -> Result<Json<Author>, Status> {
+async fn get_author(db: &State<db::Pool>, id: i32) -> Result<Json<AuthorFull>, Status> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
     schema::authors::table
@@ -102,14 +188,216 @@ async fn get_author(db: &State<db::Pool>, id: i32) -> Result<Json<Author>, Statu
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    let author = load_authors_full(&mut conn, vec![author])
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .remove(0);
+
     Ok(Json(author))
 }
 
+#[derive(Serialize)]
+struct AuthorWorks {
+    author: AuthorFull,
+    image_items: ListResponse<ImageItem>,
+    novels: ListResponse<Novel>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct AuthorMonthStats {
+    #[diesel(sql_type = Date)]
+    #[serde(with = "naive_date_format")]
+    month: NaiveDate,
+    #[diesel(sql_type = BigInt)]
+    image_items: i64,
+    #[diesel(sql_type = BigInt)]
+    files: i64,
+    #[diesel(sql_type = BigInt)]
+    nsfw_image_items: i64,
+    #[diesel(sql_type = BigInt)]
+    novels: i64,
+    #[diesel(sql_type = BigInt)]
+    nsfw_novels: i64,
+}
+
+#[derive(QueryableByName)]
+struct AppearanceRange {
+    #[diesel(sql_type = Nullable<Date>)]
+    first: Option<NaiveDate>,
+    #[diesel(sql_type = Nullable<Date>)]
+    last: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+struct AuthorStats {
+    author_id: i32,
+    image_items: i64,
+    files: i64,
+    novels: i64,
+    /// nsfw作品占全部图片和小说的比例，没有作品时为空
+    nsfw_ratio: Option<f64>,
+    #[serde(with = "naive_date_format_option")]
+    first_appearance: Option<NaiveDate>,
+    #[serde(with = "naive_date_format_option")]
+    last_appearance: Option<NaiveDate>,
+    months: Vec<AuthorMonthStats>,
+    top_tags: Vec<TagsCount>,
+}
+
+const AUTHOR_STATS_TOP_TAGS: i64 = 10;
+
+/// 作者的作品统计
+///
+/// 全部统计默认读取定时刷新的物化视图，live为true时直接计算
+#[get("/item/<id>/stats?<live>&<top_tags>")]
+async fn get_author_stats(
+    db: &State<db::Pool>,
+    id: i32,
+    live: Option<bool>,
+    top_tags: Option<i64>,
+) -> Result<Json<AuthorStats>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let (monthly_view, appearance_view, tag_view) = if live.unwrap_or(false) {
+        (
+            "author_monthly_stats_live",
+            "author_appearance_live",
+            "author_tag_stats_live",
+        )
+    } else {
+        (
+            "author_monthly_stats",
+            "author_appearance",
+            "author_tag_stats",
+        )
+    };
+
+    let months = sql_query(format!(
+        "SELECT month, image_items, files, nsfw_image_items, novels, nsfw_novels \
+         FROM {} WHERE author_id = $1 ORDER BY month ASC",
+        monthly_view
+    ))
+    .bind::<Integer, _>(id)
+    .load::<AuthorMonthStats>(&mut conn)
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    // 没有作品的作者不在视图中
+    let range = sql_query(format!(
+        "SELECT first_appearance AS first, last_appearance AS last \
+         FROM {} WHERE author_id = $1",
+        appearance_view
+    ))
+    .bind::<Integer, _>(id)
+    .get_result::<AppearanceRange>(&mut conn)
+    .await
+    .optional()
+    .map_err(|_| Status::InternalServerError)?;
+
+    let top_tags = sql_query(format!(
+        "SELECT tag, count FROM {} WHERE author_id = $1 \
+         ORDER BY count DESC, tag ASC LIMIT $2",
+        tag_view
+    ))
+    .bind::<Integer, _>(id)
+    .bind::<BigInt, _>(top_tags.unwrap_or(AUTHOR_STATS_TOP_TAGS).clamp(0, 100))
+    .load::<TagsCount>(&mut conn)
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    let image_items = months.iter().map(|v| v.image_items).sum::<i64>();
+    let novels = months.iter().map(|v| v.novels).sum::<i64>();
+    let nsfw = months
+        .iter()
+        .map(|v| v.nsfw_image_items + v.nsfw_novels)
+        .sum::<i64>();
+
+    Ok(Json(AuthorStats {
+        author_id: id,
+        image_items,
+        files: months.iter().map(|v| v.files).sum(),
+        novels,
+        nsfw_ratio: (image_items + novels > 0).then(|| nsfw as f64 / (image_items + novels) as f64),
+        first_appearance: range.as_ref().and_then(|v| v.first),
+        last_appearance: range.as_ref().and_then(|v| v.last),
+        months,
+        top_tags,
+    }))
+}
+
+/// 作者的全部作品，分页参数分别作用于图片和小说
+#[get("/item/<id>/works?<pg..>")]
+async fn get_author_works(
+    db: &State<db::Pool>,
+    id: i32,
+    pg: Pagination,
+) -> Result<Json<AuthorWorks>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let author = schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let image_items = schema::image_items::table
+        .filter(schema::image_items::author_id.eq(id))
+        .order(schema::image_items::date.desc())
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<ImageItem>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let image_items_count = schema::image_items::table
+        .filter(schema::image_items::author_id.eq(id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let novels = schema::novels::table
+        .filter(schema::novels::author_id.eq(id))
+        .order(schema::novels::created_at.desc())
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<Novel>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let novels_count = schema::novels::table
+        .filter(schema::novels::author_id.eq(id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let author = load_authors_full(&mut conn, vec![author])
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .remove(0);
+
+    Ok(Json(AuthorWorks {
+        author,
+        image_items: ListResponse::new(image_items).count(image_items_count),
+        novels: ListResponse::new(novels).count(novels_count),
+    }))
+}
+
 #[derive(Deserialize, Insertable)]
 #[diesel(table_name = schema::authors)]
 struct NewAuthorForm {
     name: String,
     urls: Vec<String>,
+    /// 通过图片上传或网络导入得到的local_files id
+    avatar_local_file_id: Option<String>,
+    banner_local_file_id: Option<String>,
 }
 
 #[post("/item", data = "<data>")]
@@ -120,12 +408,25 @@ async fn create_author(
 ) -> Result<Json<InsertResponse<i32>>, Status> {
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
-    let author_id = insert_into(schema::authors::table)
-        .values(data.into_inner())
-        .returning(schema::authors::id)
-        .get_result::<i32>(&mut conn)
+    let data = data.into_inner();
+
+    let author_id = conn
+        .transaction::<i32, diesel::result::Error, _>(|conn| {
+            async move {
+                let author_id = insert_into(schema::authors::table)
+                    .values(&data)
+                    .returning(schema::authors::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+
+                sync_author_links(conn, author_id, &data.urls).await?;
+
+                Ok(author_id)
+            }
+            .scope_boxed()
+        })
         .await
-        .map_err(|_| Status::InternalServerError)?;
+        .map_err(author_error_to_status)?;
 
     info!("Author created: {}", author_id);
 
@@ -137,11 +438,16 @@ async fn create_author(
 struct UpdateAuthorForm {
     name: Option<String>,
     urls: Option<Vec<String>>,
+    avatar_local_file_id: Option<String>,
+    banner_local_file_id: Option<String>,
 }
 
 impl UpdateAuthorForm {
     fn is_empty(&self) -> bool {
-        self.name.is_none() || self.urls.is_none()
+        self.name.is_none()
+            && self.urls.is_none()
+            && self.avatar_local_file_id.is_none()
+            && self.banner_local_file_id.is_none()
     }
 }
 
@@ -162,12 +468,26 @@ async fn update_author(
         .map_err(result_error_to_status)?;
 
     if !data.is_empty() {
-        update(schema::authors::table)
-            .filter(schema::authors::id.eq(id))
-            .set(data.into_inner())
-            .execute(&mut conn)
-            .await
-            .map_err(|_| Status::InternalServerError)?;
+        let data = data.into_inner();
+
+        conn.transaction::<(), diesel::result::Error, _>(|conn| {
+            async move {
+                update(schema::authors::table)
+                    .filter(schema::authors::id.eq(id))
+                    .set(&data)
+                    .execute(conn)
+                    .await?;
+
+                if let Some(urls) = &data.urls {
+                    sync_author_links(conn, id, urls).await?;
+                }
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(author_error_to_status)?;
     };
 
     info!("Author updated: {}", id);
@@ -200,13 +520,335 @@ async fn delete_author(
     Ok(Json(DeleteResponse { id }))
 }
 
+fn author_error_to_status(err: diesel::result::Error) -> Status {
+    match err {
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+            Status::Conflict
+        }
+        // 头像或横幅文件不存在
+        diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+            Status::UnprocessableEntity
+        }
+        err => result_error_to_status(err),
+    }
+}
+
+#[get("/item/<id>/aliases")]
+async fn list_author_aliases(
+    db: &State<db::Pool>,
+    id: i32,
+) -> Result<Json<ListResponse<AuthorAlias>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let aliases = schema::author_aliases::table
+        .filter(schema::author_aliases::author_id.eq(id))
+        .order(schema::author_aliases::id.asc())
+        .load::<AuthorAlias>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = aliases.len() as i64;
+
+    Ok(Json(ListResponse::new(aliases).count(count)))
+}
+
+#[derive(Deserialize)]
+struct NewAliasForm {
+    name: String,
+}
+
+#[post("/item/<id>/aliases", data = "<data>")]
+async fn create_author_alias(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<NewAliasForm>,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let name = data.name.trim();
+    if name.is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let alias_id = insert_into(schema::author_aliases::table)
+        .values((
+            schema::author_aliases::author_id.eq(id),
+            schema::author_aliases::name.eq(name),
+        ))
+        .returning(schema::author_aliases::id)
+        .get_result::<i32>(&mut conn)
+        .await
+        .map_err(author_error_to_status)?;
+
+    info!("Author alias created: {} for {}", alias_id, id);
+
+    Ok(Json(InsertResponse { id: alias_id }))
+}
+
+#[delete("/item/<id>/aliases/<alias_id>")]
+async fn delete_author_alias(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    alias_id: i32,
+) -> Result<Json<DeleteResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let deleted = delete(
+        schema::author_aliases::table
+            .filter(schema::author_aliases::id.eq(alias_id))
+            .filter(schema::author_aliases::author_id.eq(id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    if deleted == 0 {
+        return Err(Status::NotFound);
+    }
+
+    info!("Author alias deleted: {}", alias_id);
+
+    Ok(Json(DeleteResponse { id: alias_id }))
+}
+
+#[derive(Deserialize)]
+struct MergeAuthorForm {
+    /// 合并到的目标作者
+    into: i32,
+}
+
+/// 将重复的作者合并到目标作者
+///
+/// 图片和小说改为关联目标作者，原作者的名称和别名成为目标作者的别名，
+/// 链接并入目标作者，最后删除原作者
+#[post("/item/<id>/merge", data = "<data>")]
+async fn merge_author(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<MergeAuthorForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    if id == data.into {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let source = schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let target = schema::authors::table
+        .find(data.into)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => Status::UnprocessableEntity,
+            err => result_error_to_status(err),
+        })?;
+
+    let mut urls = target.urls.to_owned().unwrap_or_default();
+    for url in source.urls.to_owned().unwrap_or_default() {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            update(schema::image_items::table)
+                .filter(schema::image_items::author_id.eq(source.id))
+                .set(schema::image_items::author_id.eq(target.id))
+                .execute(conn)
+                .await?;
+
+            update(schema::novels::table)
+                .filter(schema::novels::author_id.eq(source.id))
+                .set(schema::novels::author_id.eq(target.id))
+                .execute(conn)
+                .await?;
+
+            // 原作者的别名转移到目标作者，重复的别名随原作者一起删除
+            sql_query(
+                "INSERT INTO author_aliases (author_id, name, created_at) \
+                 SELECT $2, name, created_at FROM author_aliases \
+                 WHERE author_id = $1 \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind::<Integer, _>(source.id)
+            .bind::<Integer, _>(target.id)
+            .execute(conn)
+            .await?;
+
+            if source.name != target.name {
+                insert_into(schema::author_aliases::table)
+                    .values((
+                        schema::author_aliases::author_id.eq(target.id),
+                        schema::author_aliases::name.eq(&source.name),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await?;
+            }
+
+            // 目标作者的名称不需要作为别名
+            delete(
+                schema::author_aliases::table
+                    .filter(schema::author_aliases::author_id.eq(target.id))
+                    .filter(schema::author_aliases::name.eq(&target.name)),
+            )
+            .execute(conn)
+            .await?;
+
+            update(schema::authors::table)
+                .filter(schema::authors::id.eq(target.id))
+                .set((
+                    schema::authors::urls.eq(&urls),
+                    // 目标作者没有头像或横幅时沿用原作者的
+                    schema::authors::avatar_local_file_id
+                        .eq(target.avatar_local_file_id.or(source.avatar_local_file_id)),
+                    schema::authors::banner_local_file_id
+                        .eq(target.banner_local_file_id.or(source.banner_local_file_id)),
+                ))
+                .execute(conn)
+                .await?;
+
+            delete(schema::authors::table.filter(schema::authors::id.eq(source.id)))
+                .execute(conn)
+                .await?;
+
+            sync_author_links(conn, target.id, urls.iter().flatten()).await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(author_error_to_status)?;
+
+    info!("Merge author {} into {}", id, data.into);
+
+    Ok(Json(UpdateResponse { id: data.into }))
+}
+
+#[get("/item/<id>/links")]
+async fn list_author_links(
+    db: &State<db::Pool>,
+    id: i32,
+) -> Result<Json<ListResponse<AuthorLink>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    schema::authors::table
+        .find(id)
+        .first::<Author>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let links = load_author_links(&mut conn, id)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = links.len() as i64;
+
+    Ok(Json(ListResponse::new(links).count(count)))
+}
+
+/// 以平台账号或链接地址查找作者
+#[get("/link?<platform>&<handle>&<url>")]
+async fn find_author_link(
+    db: &State<db::Pool>,
+    platform: Option<i16>,
+    handle: Option<String>,
+    url: Option<String>,
+) -> Result<Json<AuthorFull>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let (platform, handle) = match (platform, handle, url) {
+        // 除个人网站外的账号均以小写保存
+        (Some(platform), Some(handle), _) if platform == AuthorPlatform::Website as i16 => {
+            (platform, handle)
+        }
+        (Some(platform), Some(handle), _) => (platform, handle.to_ascii_lowercase()),
+        (_, _, Some(url)) => {
+            let link = parse_link(&url).ok_or(Status::UnprocessableEntity)?;
+            (link.platform, link.handle)
+        }
+        _ => return Err(Status::UnprocessableEntity),
+    };
+
+    let author = find_author_by_link(&mut conn, platform, &handle)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+
+    let author = load_authors_full(&mut conn, vec![author])
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .remove(0);
+
+    Ok(Json(author))
+}
+
+#[derive(Serialize)]
+struct RebuildLinksResponse {
+    authors: i64,
+    /// 与其他作者账号重复而未能重建的作者
+    conflicts: Vec<i32>,
+}
+
+/// 根据全部作者的urls重建author_links
+#[post("/links/rebuild")]
+async fn rebuild_author_links(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+) -> Result<Json<RebuildLinksResponse>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let (authors, conflicts) = author_links::rebuild_author_links(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    info!("Author links rebuilt: {}", authors);
+
+    Ok(Json(RebuildLinksResponse { authors, conflicts }))
+}
+
 pub fn routes() -> Vec<Route> {
     routes![
         list_authors,
         all_author,
         get_author,
+        get_author_works,
+        get_author_stats,
         create_author,
         update_author,
-        delete_author
+        delete_author,
+        list_author_aliases,
+        create_author_alias,
+        delete_author_alias,
+        merge_author,
+        list_author_links,
+        find_author_link,
+        rebuild_author_links
     ]
 }
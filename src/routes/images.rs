@@ -3,13 +3,18 @@ use std::ops::Deref;
 
 use crate::{
     db,
+    importers::{fetch_source_post, import_error_to_status, import_source_post, SourcePost},
     models::*,
+    routes::authors::{load_authors_full, AuthorFull},
+    routes::tags::{normalize_tags, resolve_tag_map, resolve_tags, split_tags, TagsCount},
     schema,
+    tag_query::{build_tag_query_filter, parse_bool_field, parse_tag_query, BoxedFilter},
     utils::{
         naive_date_format, naive_date_format_option, parse_order_from_string, response::*,
         result_error_to_status, result_error_to_status_failed_dependency, sdk_error_to_status,
         ApiTokenClaims, Pagination, PaginationHighLimit, TransactionError,
     },
+    AppState,
 };
 use aws_sdk_s3::operation::put_object::PutObjectError;
 use chrono::NaiveDate;
@@ -20,9 +25,10 @@ use serde::{Deserialize, Serialize};
 
 use diesel::{
     delete, insert_into, result::DatabaseErrorKind, update, AsChangeset, BelongingToDsl,
-    ExpressionMethods, GroupedBy, QueryDsl, SelectableHelper,
+    ExpressionMethods, GroupedBy, NullableExpressionMethods, PgArrayExpressionMethods,
+    PgTextExpressionMethods, QueryDsl, SelectableHelper,
 };
-use diesel::dsl::sql;
+use diesel::dsl::{not, sql};
 use diesel::sql_types::BigInt;
 use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
 use log::info;
@@ -31,19 +37,49 @@ use log::info;
 struct ImageItemFull {
     #[serde(flatten)]
     image_item: ImageItem,
-    author: Option<Author>,
+    author: Option<AuthorFull>,
     local_files: Vec<LocalFile>,
 }
 
-#[get("/item?<id>&<date>&<author_id>&<nsfw>&<pg..>")]
+/// 标签查询中可用的字段
+const IMAGE_QUERY_FIELDS: &[&str] = &["author", "nsfw"];
+
+fn image_tag_filter(tag: String) -> BoxedFilter<schema::image_items::table> {
+    Box::new(schema::image_items::tags.contains(vec![tag]))
+}
+
+fn image_field_filter(
+    name: &str,
+    value: &str,
+) -> Result<BoxedFilter<schema::image_items::table>, String> {
+    match name {
+        "author" => Ok(Box::new(
+            schema::image_items::author_id.eq_any(
+                schema::authors::table
+                    .filter(schema::authors::name.ilike(value.to_owned()))
+                    .select(schema::authors::id.nullable()),
+            ),
+        )),
+        "nsfw" => Ok(Box::new(
+            schema::image_items::nsfw.eq(parse_bool_field(name, value)?),
+        )),
+        _ => Err(format!("unknown field {}", name)),
+    }
+}
+
+#[get("/item?<id>&<date>&<author_id>&<nsfw>&<tags>&<any_tags>&<exclude_tags>&<q>&<pg..>")]
 async fn list_image_items(
     db: &State<db::Pool>,
     id: Option<i32>,
     date: Option<String>,
     author_id: Option<i32>,
     nsfw: Option<bool>,
+    tags: Option<String>,
+    any_tags: Option<String>,
+    exclude_tags: Option<String>,
+    q: Option<String>,
     pg: PaginationHighLimit,
-) -> Result<Json<ListResponse<ImageItemFull>>, Status> {
+) -> Result<Json<ListResponse<ImageItemFull>>, ApiError> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
     let mut query = schema::image_items::table
@@ -77,6 +113,51 @@ async fn list_image_items(
         query_count = query_count.filter(schema::image_items::nsfw.eq(val));
     };
 
+    // 包含全部标签
+    if let Some(val) = tags {
+        let tags = resolve_tags(&mut conn, &split_tags(&val))
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+        query = query.filter(schema::image_items::tags.contains(tags.to_owned()));
+        query_count = query_count.filter(schema::image_items::tags.contains(tags));
+    };
+
+    // 包含任一标签
+    if let Some(val) = any_tags {
+        let tags = resolve_tags(&mut conn, &split_tags(&val))
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+        query = query.filter(schema::image_items::tags.overlaps_with(tags.to_owned()));
+        query_count = query_count.filter(schema::image_items::tags.overlaps_with(tags));
+    };
+
+    // 不包含任何标签
+    if let Some(val) = exclude_tags {
+        let tags = resolve_tags(&mut conn, &split_tags(&val))
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+        query = query.filter(not(schema::image_items::tags.overlaps_with(tags.to_owned())));
+        query_count = query_count.filter(not(schema::image_items::tags.overlaps_with(tags)));
+    };
+
+    // 以标签查询语法筛选
+    if let Some(val) = q {
+        if let Some(tag_query) = parse_tag_query(&val, IMAGE_QUERY_FIELDS)? {
+            let resolved = resolve_tag_map(&mut conn, tag_query.tags())
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+            let filter = build_tag_query_filter(
+                &tag_query,
+                &resolved,
+                &image_tag_filter,
+                &image_field_filter,
+            )?;
+
+            query = query.filter(filter.clone());
+            query_count = query_count.filter(filter);
+        }
+    };
+
     // 顺序选择
     for orders in parse_order_from_string(pg.order_by) {
         if let Some(order) = orders {
@@ -101,7 +182,23 @@ async fn list_image_items(
 
     let image_items: Vec<ImageItem> = items_batch.iter().map(|item| item.0.to_owned()).collect();
 
-    let authors: Vec<Option<Author>> = items_batch.iter().map(|item| item.1.to_owned()).collect();
+    let author_map = load_authors_full(
+        &mut conn,
+        items_batch
+            .iter()
+            .filter_map(|item| item.1.to_owned())
+            .collect(),
+    )
+    .await
+    .map_err(|_| Status::InternalServerError)?
+    .into_iter()
+    .map(|v| (v.author.id, v))
+    .collect::<HashMap<i32, AuthorFull>>();
+
+    let authors: Vec<Option<AuthorFull>> = image_items
+        .iter()
+        .map(|item| item.author_id.and_then(|id| author_map.get(&id).cloned()))
+        .collect();
 
     let all_local_files: Vec<(ImageItemLocalFile, LocalFile)> =
         ImageItemLocalFile::belonging_to(&image_items)
@@ -175,10 +272,14 @@ async fn list_image_items_by_date(
         )
         .load::<Author>(&mut conn)
         .await
-        .expect("Error loading authors")
-        .iter()
-        .map(|v| (v.id, v.to_owned()))
-        .collect::<Vec<(i32, Author)>>();
+        .expect("Error loading authors");
+
+    let authors = load_authors_full(&mut conn, authors)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .into_iter()
+        .map(|v| (v.author.id, v))
+        .collect::<Vec<(i32, AuthorFull)>>();
 
     let mut author_map = HashMap::new();
     for (id, author) in &authors {
@@ -258,9 +359,14 @@ async fn get_image_item(db: &State<db::Pool>, id: i32) -> Result<Json<ImageItemF
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    let author = load_authors_full(&mut conn, item.1.into_iter().collect())
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .pop();
+
     Ok(Json(ImageItemFull {
         image_item: item.0,
-        author: item.1,
+        author,
         local_files: local_file_items,
     }))
 }
@@ -273,6 +379,8 @@ struct NewImageItemForm {
     #[serde(with = "naive_date_format")]
     date: NaiveDate,
     nsfw: bool,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 #[post("/item", data = "<data>")]
@@ -284,6 +392,10 @@ async fn create_image_item(
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
+    let tags = normalize_tags(&mut conn, &data.tags)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
     let image_item_id = conn
         .transaction::<i32, TransactionError<PutObjectError>, _>(|conn| {
             async move {
@@ -293,21 +405,13 @@ async fn create_image_item(
                         schema::image_items::urls.eq(&data.urls),
                         schema::image_items::date.eq(&data.date),
                         schema::image_items::nsfw.eq(data.nsfw),
+                        schema::image_items::tags.eq(&tags),
                     ))
                     .returning(schema::image_items::id)
                     .get_result::<i32>(conn)
                     .await
                     .map_err(|err| TransactionError::ResultError(err))?;
 
-                insert_into(schema::image_items_grouped::table)
-                    .values((
-                        schema::image_items_grouped::image_item_id.eq(image_item_id),
-                        schema::image_items_grouped::date.eq(data.date),
-                    ))
-                    .execute(conn)
-                    .await
-                    .map_err(|err| TransactionError::ResultError(err))?;
-
                 if let Some(local_file_ids) = data.local_file_ids.to_owned() {
                     for local_file_id in local_file_ids {
                         schema::local_files::table
@@ -342,6 +446,47 @@ async fn create_image_item(
     Ok(Json(InsertResponse { id: image_item_id }))
 }
 
+/// 解析作品页面，返回将要导入的内容，不下载图片
+#[post("/source_preview", data = "<url>")]
+async fn preview_source(
+    app_state: &State<AppState>,
+    auth: Option<ApiTokenClaims>,
+    url: String,
+) -> Result<Json<SourcePost>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+
+    Ok(Json(
+        fetch_source_post(&app_state.web_fetcher, &url)
+            .await
+            .map_err(import_error_to_status)?,
+    ))
+}
+
+/// 从pixiv、Twitter或Danbooru的作品页面导入全部图片，并关联作者和标签
+#[post("/item_from_source", data = "<url>")]
+async fn create_image_item_from_source(
+    app_state: &State<AppState>,
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    url: String,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let image_item_id = import_source_post(
+        &mut conn,
+        &app_state.s3_client,
+        &app_state.web_fetcher,
+        &url,
+    )
+    .await
+    .map_err(import_error_to_status)?;
+
+    info!("Create image item from source: {}", image_item_id);
+
+    Ok(Json(InsertResponse { id: image_item_id }))
+}
+
 #[derive(Deserialize, Clone, Debug)]
 struct UpdateImageItemForm {
     local_file_ids: Option<Vec<String>>,
@@ -350,6 +495,7 @@ struct UpdateImageItemForm {
     date: Option<NaiveDate>,
     nsfw: Option<bool>,
     author_id: Option<i32>,
+    tags: Option<Vec<String>>,
 }
 
 #[derive(AsChangeset)]
@@ -359,6 +505,7 @@ struct ImageItemForUpdate {
     date: Option<NaiveDate>,
     nsfw: Option<bool>,
     author_id: Option<i32>,
+    tags: Option<Vec<String>>,
 }
 
 impl From<UpdateImageItemForm> for ImageItemForUpdate {
@@ -368,13 +515,17 @@ impl From<UpdateImageItemForm> for ImageItemForUpdate {
             date: value.date,
             nsfw: value.nsfw,
             author_id: value.author_id,
+            tags: value.tags,
         }
     }
 }
 
 impl ImageItemForUpdate {
     fn is_empty(&self) -> bool {
-        self.urls.is_none() && self.date.is_none() && self.author_id.is_none()
+        self.urls.is_none()
+            && self.date.is_none()
+            && self.author_id.is_none()
+            && self.tags.is_none()
     }
 }
 
@@ -390,7 +541,7 @@ async fn update_image_item(
 
     let data = data.deref();
 
-    let update_data: ImageItemForUpdate = data.to_owned().into();
+    let mut update_data: ImageItemForUpdate = data.to_owned().into();
 
     schema::image_items::table
         .find(id)
@@ -398,6 +549,14 @@ async fn update_image_item(
         .await
         .map_err(result_error_to_status)?;
 
+    if let Some(tags) = &update_data.tags {
+        update_data.tags = Some(
+            normalize_tags(&mut conn, tags)
+                .await
+                .map_err(|_| Status::InternalServerError)?,
+        );
+    }
+
     conn.transaction::<(), diesel::result::Error, _>(|conn| {
         let data = data.to_owned();
         async move {
@@ -409,14 +568,6 @@ async fn update_image_item(
                     .await?;
             };
 
-            if let Some(date) = data.date {
-                update(schema::image_items_grouped::table)
-                    .filter(schema::image_items_grouped::image_item_id.eq(id))
-                    .set(schema::image_items_grouped::date.eq(date))
-                    .execute(conn)
-                    .await?;
-            }
-
             if let Some(local_file_ids) = data.local_file_ids {
                 delete(schema::image_items_local_files::table)
                     .filter(schema::image_items_local_files::image_item_id.eq(id))
@@ -483,13 +634,39 @@ async fn delete_image_item(
     Ok(Json(DeleteResponse { id }))
 }
 
+#[get("/tags_count")]
+async fn count_tags(db: &State<db::Pool>) -> Result<Json<Vec<TagsCount>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    // 与小说共用标签，数量由触发器维护
+    let results = schema::tags::table
+        .filter(schema::tags::alias_of.is_null())
+        .filter(schema::tags::image_count.gt(0))
+        .order(schema::tags::image_count.desc())
+        .select((schema::tags::key, schema::tags::image_count))
+        .load::<(String, i32)>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .into_iter()
+        .map(|(tag, count)| TagsCount {
+            tag,
+            count: count.into(),
+        })
+        .collect::<Vec<TagsCount>>();
+
+    Ok(Json(results))
+}
+
 pub fn routes() -> Vec<Route> {
     routes![
         list_image_items,
         list_image_items_by_date,
         create_image_item,
+        preview_source,
+        create_image_item_from_source,
         get_image_item,
         update_image_item,
-        delete_image_item
+        delete_image_item,
+        count_tags
     ]
 }
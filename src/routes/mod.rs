@@ -1,5 +1,10 @@
+pub mod admin;
 pub mod auth;
 pub mod authors;
 pub mod images;
+pub mod novel_chapters;
 pub mod novels;
+pub mod series;
 pub mod storage;
+pub mod tags;
+pub mod tasks;
@@ -0,0 +1,433 @@
+use crate::{
+    db,
+    markdown::render_markdown_cached,
+    models::*,
+    novel_metadata::count_words,
+    schema,
+    utils::{
+        datetime_format_option, parse_order_from_string,
+        response::{DeleteResponse, InsertResponse, ListResponse, UpdateResponse},
+        result_error_to_status, ApiTokenClaims, Pagination,
+    },
+};
+use chrono::{DateTime, Utc};
+use diesel::{
+    delete, insert_into, pg::Pg, query_builder::AsChangeset, update, ExpressionMethods,
+    OptionalExtension, QueryDsl, Queryable, Selectable, SelectableHelper,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use diesel_order_with_direction::OrderWithDirectionDsl;
+use log::info;
+use rocket::{delete, get, http::Status, post, put, serde::json::Json, Route, State};
+use serde::{Deserialize, Serialize};
+
+/// 目录及上一章/下一章导航中使用的章节信息，不包含正文
+#[derive(Queryable, Selectable, Serialize, Clone, Debug)]
+#[diesel(table_name = schema::novel_chapters)]
+struct ChapterSummary {
+    id: i32,
+    position: i32,
+    title: String,
+    word_count: i32,
+    #[serde(with = "datetime_format_option")]
+    published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ChapterFull {
+    #[serde(flatten)]
+    chapter: NovelChapter,
+    /// 由Markdown格式的content渲染
+    content_html: String,
+    prev: Option<ChapterSummary>,
+    next: Option<ChapterSummary>,
+}
+
+/// 未登录时只能看到已发布的章节
+fn visible_chapters<'a>(
+    novel_id: i32,
+    show_unpublished: bool,
+) -> schema::novel_chapters::BoxedQuery<'a, Pg> {
+    let query = schema::novel_chapters::table
+        .filter(schema::novel_chapters::novel_id.eq(novel_id))
+        .into_boxed();
+
+    if show_unpublished {
+        query
+    } else {
+        query.filter(schema::novel_chapters::published_at.le(Utc::now()))
+    }
+}
+
+async fn find_novel(conn: &mut AsyncPgConnection, id: i32) -> Result<Novel, Status> {
+    schema::novels::table
+        .find(id)
+        .first::<Novel>(conn)
+        .await
+        .map_err(result_error_to_status)
+}
+
+async fn find_chapter(
+    conn: &mut AsyncPgConnection,
+    novel_id: i32,
+    chapter_id: i32,
+) -> Result<NovelChapter, Status> {
+    schema::novel_chapters::table
+        .filter(schema::novel_chapters::novel_id.eq(novel_id))
+        .filter(schema::novel_chapters::id.eq(chapter_id))
+        .first::<NovelChapter>(conn)
+        .await
+        .map_err(result_error_to_status)
+}
+
+async fn last_position(
+    conn: &mut AsyncPgConnection,
+    novel_id: i32,
+) -> Result<i32, diesel::result::Error> {
+    Ok(schema::novel_chapters::table
+        .filter(schema::novel_chapters::novel_id.eq(novel_id))
+        .order(schema::novel_chapters::position.desc())
+        .select(schema::novel_chapters::position)
+        .first::<i32>(conn)
+        .await
+        .optional()?
+        .unwrap_or(0))
+}
+
+#[get("/item/<id>/toc")]
+async fn get_toc(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+) -> Result<Json<ListResponse<ChapterSummary>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    find_novel(&mut conn, id).await?;
+
+    let chapters = visible_chapters(id, auth.is_some())
+        .order(schema::novel_chapters::position.asc())
+        .select(ChapterSummary::as_select())
+        .load::<ChapterSummary>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = chapters.len() as i64;
+
+    Ok(Json(ListResponse::new(chapters).count(count)))
+}
+
+#[get("/item/<id>/chapters?<pg..>")]
+async fn list_chapters(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    pg: Pagination,
+) -> Result<Json<ListResponse<NovelChapter>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    find_novel(&mut conn, id).await?;
+
+    let show_unpublished = auth.is_some();
+
+    let mut query = visible_chapters(id, show_unpublished);
+
+    // 顺序选择
+    for order in parse_order_from_string(pg.order_by).into_iter().flatten() {
+        query =
+            match order.column.as_str() {
+                "id" => query.then_order_by_with_dir(order.direction, schema::novel_chapters::id),
+                "position" => {
+                    query.then_order_by_with_dir(order.direction, schema::novel_chapters::position)
+                }
+                "title" => {
+                    query.then_order_by_with_dir(order.direction, schema::novel_chapters::title)
+                }
+                "word_count" => query
+                    .then_order_by_with_dir(order.direction, schema::novel_chapters::word_count),
+                "published_at" => query
+                    .then_order_by_with_dir(order.direction, schema::novel_chapters::published_at),
+                "created_at" => query
+                    .then_order_by_with_dir(order.direction, schema::novel_chapters::created_at),
+                "updated_at" => query
+                    .then_order_by_with_dir(order.direction, schema::novel_chapters::updated_at),
+                _ => query,
+            }
+    }
+
+    let chapters = query
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<NovelChapter>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = visible_chapters(id, show_unpublished)
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(ListResponse::new(chapters).count(count)))
+}
+
+#[get("/item/<id>/chapters/<chapter_id>")]
+async fn get_chapter(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    chapter_id: i32,
+) -> Result<Json<ChapterFull>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let show_unpublished = auth.is_some();
+
+    let chapter = visible_chapters(id, show_unpublished)
+        .filter(schema::novel_chapters::id.eq(chapter_id))
+        .first::<NovelChapter>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let prev = visible_chapters(id, show_unpublished)
+        .filter(schema::novel_chapters::position.lt(chapter.position))
+        .order(schema::novel_chapters::position.desc())
+        .select(ChapterSummary::as_select())
+        .first::<ChapterSummary>(&mut conn)
+        .await
+        .ok();
+
+    let next = visible_chapters(id, show_unpublished)
+        .filter(schema::novel_chapters::position.gt(chapter.position))
+        .order(schema::novel_chapters::position.asc())
+        .select(ChapterSummary::as_select())
+        .first::<ChapterSummary>(&mut conn)
+        .await
+        .ok();
+
+    let content_html = render_markdown_cached(&mut conn, &chapter.content)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(ChapterFull {
+        chapter,
+        content_html,
+        prev,
+        next,
+    }))
+}
+
+#[derive(Deserialize)]
+struct NewChapterForm {
+    /// 插入的位置，从1开始，未提供时追加到最后
+    position: Option<i32>,
+    title: String,
+    content: String,
+    #[serde(with = "datetime_format_option", default)]
+    published_at: Option<DateTime<Utc>>,
+}
+
+#[post("/item/<id>/chapters", data = "<data>")]
+async fn create_chapter(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<NewChapterForm>,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    find_novel(&mut conn, id).await?;
+
+    let word_count = count_words(&data.content) as i32;
+
+    let chapter_id = conn
+        .transaction::<i32, diesel::result::Error, _>(|conn| {
+            async move {
+                let next_position = last_position(conn, id).await? + 1;
+                let position = data
+                    .position
+                    .map_or(next_position, |v| v.clamp(1, next_position));
+
+                // 为插入的章节腾出位置
+                update(schema::novel_chapters::table)
+                    .filter(schema::novel_chapters::novel_id.eq(id))
+                    .filter(schema::novel_chapters::position.ge(position))
+                    .set(schema::novel_chapters::position.eq(schema::novel_chapters::position + 1))
+                    .execute(conn)
+                    .await?;
+
+                insert_into(schema::novel_chapters::table)
+                    .values((
+                        schema::novel_chapters::novel_id.eq(id),
+                        schema::novel_chapters::position.eq(position),
+                        schema::novel_chapters::title.eq(&data.title),
+                        schema::novel_chapters::content.eq(&data.content),
+                        schema::novel_chapters::word_count.eq(word_count),
+                        schema::novel_chapters::published_at.eq(data.published_at),
+                    ))
+                    .returning(schema::novel_chapters::id)
+                    .get_result::<i32>(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(result_error_to_status)?;
+
+    info!("Create novel chapter: {} ({})", chapter_id, id);
+
+    Ok(Json(InsertResponse { id: chapter_id }))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct UpdateChapterForm {
+    position: Option<i32>,
+    title: Option<String>,
+    content: Option<String>,
+    #[serde(with = "datetime_format_option", default)]
+    published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = schema::novel_chapters)]
+struct ChapterForUpdate {
+    title: Option<String>,
+    content: Option<String>,
+    word_count: Option<i32>,
+    published_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<UpdateChapterForm> for ChapterForUpdate {
+    fn from(value: UpdateChapterForm) -> Self {
+        Self {
+            word_count: value.content.as_deref().map(|v| count_words(v) as i32),
+            title: value.title,
+            content: value.content,
+            published_at: value.published_at,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+#[put("/item/<id>/chapters/<chapter_id>", data = "<data>")]
+async fn update_chapter(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    chapter_id: i32,
+    data: Json<UpdateChapterForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let chapter = find_chapter(&mut conn, id, chapter_id).await?;
+
+    let position = data.position;
+    let update_data: ChapterForUpdate = data.into_inner().into();
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            if let Some(position) = position {
+                let position = position.clamp(1, last_position(conn, id).await?);
+                let current = chapter.position;
+
+                // 移动章节时，位于新旧位置之间的章节依次前移或后移
+                if position < current {
+                    update(schema::novel_chapters::table)
+                        .filter(schema::novel_chapters::novel_id.eq(id))
+                        .filter(schema::novel_chapters::position.ge(position))
+                        .filter(schema::novel_chapters::position.lt(current))
+                        .set(
+                            schema::novel_chapters::position
+                                .eq(schema::novel_chapters::position + 1),
+                        )
+                        .execute(conn)
+                        .await?;
+                } else if position > current {
+                    update(schema::novel_chapters::table)
+                        .filter(schema::novel_chapters::novel_id.eq(id))
+                        .filter(schema::novel_chapters::position.gt(current))
+                        .filter(schema::novel_chapters::position.le(position))
+                        .set(
+                            schema::novel_chapters::position
+                                .eq(schema::novel_chapters::position - 1),
+                        )
+                        .execute(conn)
+                        .await?;
+                }
+
+                update(schema::novel_chapters::table)
+                    .filter(schema::novel_chapters::id.eq(chapter_id))
+                    .set(schema::novel_chapters::position.eq(position))
+                    .execute(conn)
+                    .await?;
+            }
+
+            update(schema::novel_chapters::table)
+                .filter(schema::novel_chapters::id.eq(chapter_id))
+                .set(update_data)
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    info!("Update novel chapter: {} ({})", chapter_id, id);
+
+    Ok(Json(UpdateResponse { id: chapter_id }))
+}
+
+#[delete("/item/<id>/chapters/<chapter_id>")]
+async fn delete_chapter(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    chapter_id: i32,
+) -> Result<Json<DeleteResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let chapter = find_chapter(&mut conn, id, chapter_id).await?;
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            delete(schema::novel_chapters::table)
+                .filter(schema::novel_chapters::id.eq(chapter_id))
+                .execute(conn)
+                .await?;
+
+            update(schema::novel_chapters::table)
+                .filter(schema::novel_chapters::novel_id.eq(id))
+                .filter(schema::novel_chapters::position.gt(chapter.position))
+                .set(schema::novel_chapters::position.eq(schema::novel_chapters::position - 1))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    info!("Delete novel chapter: {} ({})", chapter_id, id);
+
+    Ok(Json(DeleteResponse { id: chapter_id }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_toc,
+        list_chapters,
+        get_chapter,
+        create_chapter,
+        update_chapter,
+        delete_chapter
+    ]
+}
@@ -1,40 +1,106 @@
 use crate::{
     db,
+    markdown::{render_markdown_cached, render_markdown_cached_batch},
+    misc::enums::SiteContentKind,
     models::*,
+    novel_export::{build_epub, ExportBook, ExportChapter, ExportEmbed, DEFAULT_EXPORT_LANGUAGE},
+    novel_metadata::NovelMetadata,
+    routes::{
+        series::{load_series_blocks, SeriesBlock},
+        tags::{normalize_tags, resolve_tag_map, resolve_tags, split_tags, TagsCount},
+    },
     schema,
+    tag_query::{build_tag_query_filter, parse_bool_field, parse_tag_query, BoxedFilter},
+    task_queue::{TaskPayload, TaskQueue},
     utils::{
         parse_order_from_string,
-        response::{DeleteResponse, InsertResponse, ListResponse, UpdateResponse},
-        result_error_to_status, sdk_error_to_status, ApiTokenClaims, Pagination, TransactionError,
+        response::{ApiError, DeleteResponse, InsertResponse, ListResponse, UpdateResponse},
+        result_error_to_status, result_error_to_status_failed_dependency, sdk_error_to_status,
+        transaction_error_to_status, ApiTokenClaims, Pagination, TransactionError,
     },
+    AppState, BUCKET,
 };
-use aws_sdk_s3::operation::put_object::PutObjectError;
-use diesel::sql_types::{BigInt, Text};
+use aws_sdk_s3::{operation::put_object::PutObjectError, primitives::ByteStream};
+use chrono::Utc;
 use diesel::{
-    delete,
-    deserialize::{Queryable, QueryableByName},
-    insert_into,
-    query_builder::AsChangeset,
-    result::DatabaseErrorKind,
-    sql_query, update, ExpressionMethods, PgArrayExpressionMethods, QueryDsl,
-    TextExpressionMethods,
+    delete, insert_into, query_builder::AsChangeset, result::DatabaseErrorKind, update,
+    BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, OptionalExtension,
+    PgArrayExpressionMethods, PgTextExpressionMethods, QueryDsl, TextExpressionMethods,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
 };
-use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
 use diesel_order_with_direction::OrderWithDirectionDsl;
-use log::info;
-use rocket::{delete, get, http::Status, post, serde::json::Json, Route, State};
+use log::{error, info};
+use rocket::{
+    delete, get,
+    http::{ContentType, Status},
+    post,
+    serde::json::Json,
+    Route, State,
+};
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
 
 #[derive(Serialize)]
 struct ItemFull {
     #[serde(flatten)]
     novel_item: Novel,
     object: Option<SiteStorage>,
+    cover: Option<LocalFile>,
+    author: Option<Author>,
+    series: Option<SeriesBlock>,
+    /// 由Markdown格式的description渲染
+    description_html: Option<String>,
+}
+
+/// 小说及其关联文件、封面和作者
+type ItemRow = (
+    Novel,
+    Option<SiteStorage>,
+    Option<LocalFile>,
+    Option<Author>,
+);
+
+async fn render_description(
+    conn: &mut AsyncPgConnection,
+    novel: &Novel,
+) -> Result<Option<String>, diesel::result::Error> {
+    match &novel.description {
+        Some(description) => Ok(Some(render_markdown_cached(conn, description).await?)),
+        None => Ok(None),
+    }
+}
+
+/// 标签查询中可用的字段
+const NOVEL_QUERY_FIELDS: &[&str] = &["author", "nsfw"];
+
+fn novel_tag_filter(tag: String) -> BoxedFilter<schema::novels::table> {
+    Box::new(schema::novels::tags.contains(vec![tag]))
+}
+
+fn novel_field_filter(
+    name: &str,
+    value: &str,
+) -> Result<BoxedFilter<schema::novels::table>, String> {
+    match name {
+        "author" => Ok(Box::new(
+            schema::novels::author_name
+                .ilike(value.to_owned())
+                .or(schema::novels::author_id.eq_any(
+                    schema::authors::table
+                        .filter(schema::authors::name.ilike(value.to_owned()))
+                        .select(schema::authors::id.nullable()),
+                )),
+        )),
+        "nsfw" => Ok(Box::new(
+            schema::novels::nsfw.eq(parse_bool_field(name, value)?),
+        )),
+        _ => Err(format!("unknown field {}", name)),
+    }
 }
 
 #[get(
-    "/item?<id>&<title>&<description>&<tags>&<url>&<author_name>&<author_url>&<nsfw>&<object_id>&<created_by>&<pg..>"
+    "/item?<id>&<title>&<description>&<tags>&<url>&<author_name>&<author_url>&<author_id>&<nsfw>&<object_id>&<created_by>&<series_id>&<q>&<pg..>"
 )]
 async fn list_items(
     db: &State<db::Pool>,
@@ -45,15 +111,20 @@ async fn list_items(
     url: Option<String>,
     author_name: Option<String>,
     author_url: Option<String>,
+    author_id: Option<i32>,
     nsfw: Option<bool>,
     object_id: Option<i32>,
     created_by: Option<i32>,
+    series_id: Option<i32>,
+    q: Option<String>,
     pg: Pagination,
-) -> Result<Json<ListResponse<ItemFull>>, Status> {
+) -> Result<Json<ListResponse<ItemFull>>, ApiError> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
     let mut query = schema::novels::table
         .left_join(schema::site_storage::table)
+        .left_join(schema::local_files::table)
+        .left_join(schema::authors::table)
         .into_boxed();
     let mut query_count = schema::novels::table.into_boxed();
 
@@ -76,12 +147,10 @@ async fn list_items(
     };
 
     if let Some(val) = tags {
-        let tags_splited = val
-            .clone()
-            .trim()
-            .split(',')
-            .map(|v| v.trim().to_owned())
-            .collect::<Vec<String>>();
+        // 别名按其目标标签筛选
+        let tags_splited = resolve_tags(&mut conn, &split_tags(&val))
+            .await
+            .map_err(|_| Status::InternalServerError)?;
 
         query = query.filter(schema::novels::tags.contains(tags_splited.to_owned()));
         query_count = query_count.filter(schema::novels::tags.contains(tags_splited));
@@ -105,6 +174,12 @@ async fn list_items(
         query_count = query_count.filter(schema::novels::author_url.like(val));
     };
 
+    // 以author_id筛选
+    if let Some(val) = author_id {
+        query = query.filter(schema::novels::author_id.eq(val));
+        query_count = query_count.filter(schema::novels::author_id.eq(val));
+    };
+
     // 以object_id筛选
     if let Some(val) = nsfw {
         query = query.filter(schema::novels::nsfw.eq(val));
@@ -123,6 +198,39 @@ async fn list_items(
         query_count = query_count.filter(schema::novels::created_by.eq(val));
     };
 
+    // 以series_id筛选
+    if let Some(val) = series_id {
+        let series_novel_ids = schema::series_novels::table
+            .filter(schema::series_novels::series_id.eq(val))
+            .select(schema::series_novels::novel_id);
+        query = query.filter(schema::novels::id.eq_any(series_novel_ids));
+        query_count = query_count.filter(
+            schema::novels::id.eq_any(
+                schema::series_novels::table
+                    .filter(schema::series_novels::series_id.eq(val))
+                    .select(schema::series_novels::novel_id),
+            ),
+        );
+    };
+
+    // 以标签查询语法筛选
+    if let Some(val) = q {
+        if let Some(tag_query) = parse_tag_query(&val, NOVEL_QUERY_FIELDS)? {
+            let resolved = resolve_tag_map(&mut conn, tag_query.tags())
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+            let filter = build_tag_query_filter(
+                &tag_query,
+                &resolved,
+                &novel_tag_filter,
+                &novel_field_filter,
+            )?;
+
+            query = query.filter(filter.clone());
+            query_count = query_count.filter(filter);
+        }
+    };
+
     // 顺序选择
     for orders in parse_order_from_string(pg.order_by) {
         if let Some(order) = orders {
@@ -138,6 +246,9 @@ async fn list_items(
                 "author_url" => {
                     query.then_order_by_with_dir(order.direction, schema::novels::author_url)
                 }
+                "author_id" => {
+                    query.then_order_by_with_dir(order.direction, schema::novels::author_id)
+                }
                 "nsfw" => query.then_order_by_with_dir(order.direction, schema::novels::nsfw),
                 "object_id" => {
                     query.then_order_by_with_dir(order.direction, schema::novels::object_id)
@@ -153,20 +264,50 @@ async fn list_items(
         }
     }
 
-    let items_batch: Vec<(Novel, Option<SiteStorage>)> = query
+    let items_batch: Vec<ItemRow> = query
         .offset(pg.offset)
         .limit(pg.limit)
-        .load::<(Novel, Option<SiteStorage>)>(&mut conn)
+        .load::<ItemRow>(&mut conn)
         .await
         .map_err(|_| Status::InternalServerError)?;
 
-    let results = items_batch
-        .iter()
-        .map(|(novel, site_storage)| ItemFull {
-            novel_item: novel.to_owned(),
-            object: site_storage.to_owned(),
-        })
-        .collect();
+    let mut series_blocks = load_series_blocks(
+        &mut conn,
+        &items_batch
+            .iter()
+            .map(|(v, _, _, _)| v.id)
+            .collect::<Vec<i32>>(),
+    )
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    let mut descriptions_html = render_markdown_cached_batch(
+        &mut conn,
+        &items_batch
+            .iter()
+            .filter_map(|(v, _, _, _)| v.description.as_deref())
+            .collect::<Vec<&str>>(),
+    )
+    .await
+    .map_err(|_| Status::InternalServerError)?
+    .into_iter();
+
+    let mut results = Vec::new();
+    for (novel, site_storage, cover, author) in items_batch {
+        let description_html = novel
+            .description
+            .as_ref()
+            .and_then(|_| descriptions_html.next());
+
+        results.push(ItemFull {
+            series: series_blocks.remove(&novel.id),
+            novel_item: novel,
+            object: site_storage,
+            cover,
+            author,
+            description_html,
+        });
+    }
 
     let count = query_count
         .count()
@@ -187,55 +328,133 @@ async fn get_item(db: &State<db::Pool>, id: i32) -> Result<Json<ItemFull>, Statu
         .await
         .map_err(result_error_to_status)?;
 
-    let item: (Novel, Option<SiteStorage>) = schema::novels::table
+    let item: ItemRow = schema::novels::table
         .filter(schema::novels::id.eq(id))
         .left_join(schema::site_storage::table)
-        .first::<(Novel, Option<SiteStorage>)>(&mut conn)
+        .left_join(schema::local_files::table)
+        .left_join(schema::authors::table)
+        .first::<ItemRow>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let series = load_series_blocks(&mut conn, &[id])
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .remove(&id);
+
+    let description_html = render_description(&mut conn, &item.0)
         .await
         .map_err(|_| Status::InternalServerError)?;
 
     Ok(Json(ItemFull {
         novel_item: item.0,
         object: item.1,
+        cover: item.2,
+        author: item.3,
+        series,
+        description_html,
     }))
 }
 
+/// 封面生成失败不影响小说本身的写入
+async fn enqueue_cover(conn: &mut AsyncPgConnection, task_queue: &TaskQueue, novel_id: i32) {
+    match task_queue
+        .enqueue(conn, TaskPayload::NovelCover { novel_id })
+        .await
+    {
+        Ok(task_id) => info!("Novel cover task created: {} ({})", novel_id, task_id),
+        Err(err) => error!(
+            "Failed to enqueue cover task of novel {}: {:?}",
+            novel_id, err
+        ),
+    }
+}
+
 #[derive(Deserialize)]
 struct NewItemForm {
-    title: String,
+    /// 未提供时使用关联文件中提取的标题
+    title: Option<String>,
     description: Option<String>,
     url: Option<String>,
-    author_name: String,
+    /// 未提供时使用关联的作者或关联文件中提取的作者
+    author_name: Option<String>,
     author_url: Option<String>,
+    author_id: Option<i32>,
     nsfw: bool,
     tags: Vec<String>,
     object_id: i32,
     created_by: Option<i32>,
+    /// 未提供时从关联文件中生成封面
+    cover_local_file_id: Option<String>,
 }
 
 #[post("/item", data = "<data>")]
 async fn create_item(
     db: &State<db::Pool>,
+    task_queue: &State<TaskQueue>,
     auth: Option<ApiTokenClaims>,
     data: Json<NewItemForm>,
 ) -> Result<Json<InsertResponse<i32>>, Status> {
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
+    let author = match data.author_id {
+        Some(author_id) => Some(
+            schema::authors::table
+                .find(author_id)
+                .first::<Author>(&mut conn)
+                .await
+                .map_err(result_error_to_status_failed_dependency)?,
+        ),
+        None => None,
+    };
+
+    let author_name = data.author_name.to_owned().or(author.map(|v| v.name));
+
+    let (title, author_name) = match (data.title.to_owned(), author_name) {
+        (Some(title), Some(author_name)) => (title, author_name),
+        (title, author_name) => {
+            let metadata = schema::site_storage::table
+                .find(data.object_id)
+                .select(schema::site_storage::metadata)
+                .first::<Option<serde_json::Value>>(&mut conn)
+                .await
+                .map_err(result_error_to_status_failed_dependency)?
+                .and_then(|v| serde_json::from_value::<NovelMetadata>(v).ok())
+                .unwrap_or_default();
+
+            (
+                title
+                    .or(metadata.title)
+                    .ok_or(Status::UnprocessableEntity)?,
+                author_name
+                    .or(metadata.author)
+                    .ok_or(Status::UnprocessableEntity)?,
+            )
+        }
+    };
+
+    let generate_cover = data.cover_local_file_id.is_none();
+    let tags = normalize_tags(&mut conn, &data.tags)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
     let new_item_id = conn
         .transaction::<i32, TransactionError<PutObjectError>, _>(|conn| {
             async move {
                 let new_item_id = insert_into(schema::novels::table)
                     .values((
-                        schema::novels::title.eq(&data.title),
+                        schema::novels::title.eq(&title),
                         schema::novels::description.eq(&data.description),
                         schema::novels::url.eq(&data.url),
-                        schema::novels::author_name.eq(&data.author_name),
+                        schema::novels::author_name.eq(&author_name),
                         schema::novels::author_url.eq(&data.author_url),
+                        schema::novels::author_id.eq(data.author_id),
                         schema::novels::nsfw.eq(data.nsfw),
-                        schema::novels::tags.eq(&data.tags),
+                        schema::novels::tags.eq(&tags),
                         schema::novels::object_id.eq(data.object_id),
                         schema::novels::created_by.eq(data.created_by),
+                        schema::novels::cover_local_file_id.eq(&data.cover_local_file_id),
                     ))
                     .returning(schema::novels::id)
                     .get_result::<i32>(conn)
@@ -254,6 +473,10 @@ async fn create_item(
 
     info!("Create novel item: {}", new_item_id);
 
+    if generate_cover {
+        enqueue_cover(&mut conn, task_queue, new_item_id).await;
+    }
+
     Ok(Json(InsertResponse { id: new_item_id }))
 }
 
@@ -265,10 +488,12 @@ struct ItemForUpdate {
     url: Option<String>,
     author_name: Option<String>,
     author_url: Option<String>,
+    author_id: Option<i32>,
     nsfw: Option<bool>,
     tags: Option<Vec<String>>,
     object_id: Option<i32>,
     created_by: Option<i32>,
+    cover_local_file_id: Option<String>,
 }
 
 impl ItemForUpdate {
@@ -278,16 +503,19 @@ impl ItemForUpdate {
             && self.url.is_none()
             && self.author_name.is_none()
             && self.author_url.is_none()
+            && self.author_id.is_none()
             && self.nsfw.is_none()
             && self.tags.is_none()
             && self.object_id.is_none()
             && self.created_by.is_none()
+            && self.cover_local_file_id.is_none()
     }
 }
 
 #[put("/item/<id>", data = "<data>")]
 async fn update_item(
     db: &State<db::Pool>,
+    task_queue: &State<TaskQueue>,
     auth: Option<ApiTokenClaims>,
     id: i32,
     data: Json<ItemForUpdate>,
@@ -295,7 +523,7 @@ async fn update_item(
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let data = data.deref();
+    let mut data = data.into_inner();
 
     schema::novels::table
         .find(id)
@@ -303,10 +531,18 @@ async fn update_item(
         .await
         .map_err(result_error_to_status)?;
 
+    if let Some(tags) = &data.tags {
+        data.tags = Some(
+            normalize_tags(&mut conn, tags)
+                .await
+                .map_err(|_| Status::InternalServerError)?,
+        );
+    }
+
     if !data.is_empty() {
         update(schema::novels::table)
             .filter(schema::novels::id.eq(id))
-            .set(data)
+            .set(&data)
             .execute(&mut conn)
             .await
             .map_err(|err| {
@@ -324,6 +560,11 @@ async fn update_item(
 
     info!("Update novel item: {}", id);
 
+    // 更换了关联文件但没有指定封面时重新生成
+    if data.object_id.is_some() && data.cover_local_file_id.is_none() {
+        enqueue_cover(&mut conn, task_queue, id).await;
+    }
+
     Ok(Json(UpdateResponse { id }))
 }
 
@@ -352,26 +593,322 @@ async fn delete_item(
     Ok(Json(DeleteResponse { id }))
 }
 
-#[derive(Serialize, QueryableByName)]
-struct TagsCount {
-    #[sql_type = "Text"]
-    tag: String,
-    #[sql_type = "BigInt"]
-    count: i64,
-}
-
 #[get("/tags_count")]
 async fn count_tags(db: &State<db::Pool>) -> Result<Json<Vec<TagsCount>>, Status> {
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let results: Vec<TagsCount> = sql_query("SELECT tag, COUNT(*) FROM (select UNNEST(tags) AS tag FROM novels) t GROUP BY tag ORDER BY count DESC")
-    .load(&mut conn)
+    // 数量由触发器维护，别名不会出现在小说中
+    let results = schema::tags::table
+        .filter(schema::tags::alias_of.is_null())
+        .filter(schema::tags::novel_count.gt(0))
+        .order(schema::tags::novel_count.desc())
+        .select((schema::tags::key, schema::tags::novel_count))
+        .load::<(String, i32)>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .into_iter()
+        .map(|(tag, count)| TagsCount {
+            tag,
+            count: count.into(),
+        })
+        .collect::<Vec<TagsCount>>();
+
+    Ok(Json(results))
+}
+
+const NOVEL_EXPORT_PREFIX: &str = "novel/export/";
+
+/// 用于查找同一小说的旧导出文件
+#[derive(Serialize)]
+struct ExportMetadata {
+    novel_id: i32,
+    format: &'static str,
+}
+
+async fn read_object(app_state: &AppState, key: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(app_state
+        .s3_client
+        .get_object()
+        .bucket(BUCKET)
+        .key(key)
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes()
+        .to_vec())
+}
+
+/// 删除同一小说内容已过期的导出记录，对应的S3对象加入待删除列表并返回其key
+async fn delete_stale_exports(
+    conn: &mut AsyncPgConnection,
+    metadata: &serde_json::Value,
+    hash: &str,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let stale_keys = delete(schema::site_storage::table)
+        .filter(schema::site_storage::kind.eq(SiteContentKind::NovelExport as i16))
+        .filter(schema::site_storage::metadata.eq(metadata))
+        .filter(schema::site_storage::hash.ne(hash))
+        .returning(schema::site_storage::key)
+        .get_results::<String>(conn)
+        .await?;
+
+    if !stale_keys.is_empty() {
+        insert_into(schema::orphaned_objects::table)
+            .values(
+                stale_keys
+                    .iter()
+                    .map(|key| schema::orphaned_objects::key.eq(key))
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(stale_keys)
+}
+
+/// 事务提交后删除过期导出文件，失败的由purge_orphaned_objects任务重试
+async fn purge_stale_exports(
+    conn: &mut AsyncPgConnection,
+    app_state: &AppState,
+    stale_keys: Vec<String>,
+) {
+    for key in stale_keys {
+        if let Err(err) = app_state
+            .s3_client
+            .delete_object()
+            .bucket(BUCKET)
+            .key(&key)
+            .send()
+            .await
+        {
+            error!("Failed to delete stale export {}: {:?}", key, err);
+            continue;
+        }
+
+        if let Err(err) = delete(schema::orphaned_objects::table)
+            .filter(schema::orphaned_objects::key.eq(&key))
+            .execute(conn)
+            .await
+        {
+            error!("Failed to remove orphaned object record {}: {:?}", key, err);
+        }
+    }
+}
+
+/// 将已发布的章节导出为EPUB，内容未变化时直接返回缓存的文件
+///
+/// 未登录时只返回已缓存的文件，生成新文件需要登录
+#[get("/item/<id>/export/epub")]
+async fn export_epub(
+    app_state: &State<AppState>,
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+) -> Result<Json<SiteStorage>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let novel = schema::novels::table
+        .find(id)
+        .first::<Novel>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let chapters = schema::novel_chapters::table
+        .filter(schema::novel_chapters::novel_id.eq(id))
+        .filter(schema::novel_chapters::published_at.le(Utc::now()))
+        .order(schema::novel_chapters::position.asc())
+        .load::<NovelChapter>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    if chapters.is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let language = match novel.object_id {
+        Some(object_id) => schema::site_storage::table
+            .find(object_id)
+            .select(schema::site_storage::metadata)
+            .first::<Option<serde_json::Value>>(&mut conn)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|v| serde_json::from_value::<NovelMetadata>(v).ok())
+            .and_then(|v| v.language),
+        None => None,
+    };
+
+    let book = ExportBook {
+        novel_id: id,
+        title: novel.title.to_owned(),
+        author: novel.author_name.to_owned(),
+        description: novel.description.to_owned(),
+        language: language.unwrap_or_else(|| DEFAULT_EXPORT_LANGUAGE.to_owned()),
+        cover_local_file_id: novel.cover_local_file_id.to_owned(),
+        chapters: chapters
+            .into_iter()
+            .map(|v| ExportChapter {
+                id: v.id,
+                title: v.title,
+                content: v.content,
+            })
+            .collect(),
+    };
+
+    let hash = book.content_hash();
+
+    let cached = schema::site_storage::table
+        .filter(schema::site_storage::hash.eq(&hash))
+        .filter(schema::site_storage::kind.eq(SiteContentKind::NovelExport as i16))
+        .first::<SiteStorage>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| Status::InternalServerError)?;
+
+    if let Some(cached) = cached {
+        return Ok(Json(cached));
+    }
+
+    auth.ok_or(Status::Forbidden)?;
+
+    // 封面读取失败时导出不带封面的文件
+    let cover = match &novel.cover_local_file_id {
+        Some(cover_id) => {
+            let cover = match schema::local_files::table
+                .find(cover_id)
+                .first::<LocalFile>(&mut conn)
+                .await
+            {
+                Ok(local_file) => read_object(app_state, &local_file.path).await,
+                Err(err) => Err(err.into()),
+            };
+
+            cover
+                .map_err(|err| error!("Failed to read cover of novel {}: {:#}", id, err))
+                .ok()
+        }
+        None => None,
+    };
+
+    // 嵌入图片读取失败时只保留说明文字
+    let embed_ids = book.embed_ids();
+    let embed_files = if embed_ids.is_empty() {
+        Vec::new()
+    } else {
+        schema::local_files::table
+            .filter(schema::local_files::id.eq_any(&embed_ids))
+            .load::<LocalFile>(&mut conn)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+    };
+
+    let mut embeds = Vec::with_capacity(embed_files.len());
+    for local_file in embed_files {
+        match read_object(app_state, &local_file.path).await {
+            Ok(data) => embeds.push(ExportEmbed {
+                local_file_id: local_file.id,
+                data,
+            }),
+            Err(err) => error!(
+                "Failed to read embed {} of novel {}: {:#}",
+                local_file.id, id, err
+            ),
+        }
+    }
+
+    let identifier = format!("urn:md5:{}", hash);
+    let data = tokio::task::spawn_blocking(move || {
+        build_epub(&book, &identifier, cover.as_deref(), &embeds)
+    })
     .await
+    .map_err(|_| Status::InternalServerError)?
+    .map_err(|err| {
+        error!("Failed to build epub of novel {}: {:#}", id, err);
+        Status::InternalServerError
+    })?;
+
+    let filename = format!("{}.epub", hash);
+    let key = format!("{}{}", NOVEL_EXPORT_PREFIX, filename);
+    let metadata = serde_json::to_value(ExportMetadata {
+        novel_id: id,
+        format: "epub",
+    })
     .map_err(|_| Status::InternalServerError)?;
 
-    Ok(Json(results))
+    let (object, stale_keys) = conn
+        .transaction::<_, TransactionError<PutObjectError>, _>(|conn| {
+            let content_type = ContentType::EPUB;
+            async move {
+                let object = insert_into(schema::site_storage::table)
+                    .values((
+                        schema::site_storage::file_name.eq(&filename),
+                        schema::site_storage::key.eq(&key),
+                        schema::site_storage::size.eq(data.len() as i64),
+                        schema::site_storage::hash.eq(&hash),
+                        schema::site_storage::kind.eq(SiteContentKind::NovelExport as i16),
+                        schema::site_storage::mime_type.eq(content_type.to_string()),
+                        schema::site_storage::metadata.eq(Some(&metadata)),
+                    ))
+                    .on_conflict((schema::site_storage::kind, schema::site_storage::hash))
+                    .do_nothing()
+                    .get_result::<SiteStorage>(conn)
+                    .await
+                    .optional()
+                    .map_err(TransactionError::ResultError)?;
+
+                // 同时发起的导出已经写入了相同的文件
+                let Some(object) = object else {
+                    let object = schema::site_storage::table
+                        .filter(schema::site_storage::hash.eq(&hash))
+                        .filter(schema::site_storage::kind.eq(SiteContentKind::NovelExport as i16))
+                        .first::<SiteStorage>(conn)
+                        .await
+                        .map_err(TransactionError::ResultError)?;
+                    return Ok((object, Vec::new()));
+                };
+
+                app_state
+                    .s3_client
+                    .put_object()
+                    .body(ByteStream::from(data.to_owned()))
+                    .bucket(BUCKET)
+                    .content_type(content_type.to_string())
+                    .content_length(data.len() as i64)
+                    .key(&key)
+                    .send()
+                    .await
+                    .map_err(TransactionError::SdkError)?;
+
+                let stale_keys = delete_stale_exports(conn, &metadata, &hash)
+                    .await
+                    .map_err(TransactionError::ResultError)?;
+
+                Ok((object, stale_keys))
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(transaction_error_to_status)?;
+
+    purge_stale_exports(&mut conn, app_state, stale_keys).await;
+
+    info!("Novel exported: {} ({})", id, object.id);
+
+    Ok(Json(object))
 }
 
 pub fn routes() -> Vec<Route> {
-    routes![list_items, get_item, create_item, update_item, delete_item, count_tags]
+    routes![
+        list_items,
+        get_item,
+        create_item,
+        update_item,
+        delete_item,
+        count_tags,
+        export_epub
+    ]
 }
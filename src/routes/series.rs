@@ -0,0 +1,422 @@
+use crate::{
+    db,
+    markdown::render_markdown_cached,
+    models::*,
+    schema,
+    utils::{
+        parse_order_from_string,
+        response::{DeleteResponse, InsertResponse, ListResponse, UpdateResponse},
+        result_error_to_status, ApiTokenClaims, Pagination,
+    },
+};
+use diesel::{
+    delete, insert_into, query_builder::AsChangeset, result::DatabaseErrorKind, update,
+    BoolExpressionMethods, ExpressionMethods, QueryDsl, TextExpressionMethods,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use diesel_order_with_direction::OrderWithDirectionDsl;
+use log::info;
+use rocket::{delete, get, http::Status, post, put, serde::json::Json, Route, State};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SeriesVolume {
+    pub novel_id: i32,
+    pub title: String,
+    pub position: i32,
+}
+
+/// 小说详情中的系列信息
+#[derive(Serialize, Clone, Debug)]
+pub struct SeriesBlock {
+    pub id: i32,
+    pub title: String,
+    pub position: i32,
+    pub prev: Option<SeriesVolume>,
+    pub next: Option<SeriesVolume>,
+}
+
+/// 批量读取小说所属系列及其前后卷，返回以小说id为键的结果
+pub async fn load_series_blocks(
+    conn: &mut AsyncPgConnection,
+    novel_ids: &[i32],
+) -> Result<HashMap<i32, SeriesBlock>, diesel::result::Error> {
+    let series_ids = schema::series_novels::table
+        .filter(schema::series_novels::novel_id.eq_any(novel_ids))
+        .select(schema::series_novels::series_id)
+        .distinct()
+        .load::<i32>(conn)
+        .await?;
+
+    if series_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let series = schema::series::table
+        .filter(schema::series::id.eq_any(&series_ids))
+        .load::<Series>(conn)
+        .await?
+        .into_iter()
+        .map(|v| (v.id, v))
+        .collect::<HashMap<i32, Series>>();
+
+    let volumes = schema::series_novels::table
+        .inner_join(schema::novels::table)
+        .filter(schema::series_novels::series_id.eq_any(&series_ids))
+        .order((
+            schema::series_novels::series_id.asc(),
+            schema::series_novels::position.asc(),
+        ))
+        .select((
+            schema::series_novels::series_id,
+            schema::series_novels::novel_id,
+            schema::novels::title,
+            schema::series_novels::position,
+        ))
+        .load::<(i32, i32, String, i32)>(conn)
+        .await?;
+
+    let mut blocks = HashMap::new();
+
+    for (index, (series_id, novel_id, _, position)) in volumes.iter().enumerate() {
+        if !novel_ids.contains(novel_id) {
+            continue;
+        }
+
+        let Some(series) = series.get(series_id) else {
+            continue;
+        };
+
+        let volume_at = |index: usize| {
+            volumes
+                .get(index)
+                .filter(|(v, _, _, _)| v == series_id)
+                .map(|(_, novel_id, title, position)| SeriesVolume {
+                    novel_id: *novel_id,
+                    title: title.to_owned(),
+                    position: *position,
+                })
+        };
+
+        blocks.insert(
+            *novel_id,
+            SeriesBlock {
+                id: series.id,
+                title: series.title.to_owned(),
+                position: *position,
+                prev: index.checked_sub(1).and_then(volume_at),
+                next: volume_at(index + 1),
+            },
+        );
+    }
+
+    Ok(blocks)
+}
+
+#[derive(Serialize)]
+struct SeriesFull {
+    #[serde(flatten)]
+    series: Series,
+    volumes: Vec<SeriesVolume>,
+    /// 由Markdown格式的description渲染
+    description_html: Option<String>,
+}
+
+#[get("/item?<id>&<title>&<pg..>")]
+async fn list_series(
+    db: &State<db::Pool>,
+    id: Option<i32>,
+    title: Option<String>,
+    pg: Pagination,
+) -> Result<Json<ListResponse<Series>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let mut query = schema::series::table.into_boxed();
+    let mut query_count = schema::series::table.into_boxed();
+
+    // 以id筛选
+    if let Some(val) = id {
+        query = query.filter(schema::series::id.eq(val));
+        query_count = query_count.filter(schema::series::id.eq(val));
+    };
+
+    // 以title筛选
+    if let Some(val) = title {
+        query = query.filter(schema::series::title.like(val.to_owned()));
+        query_count = query_count.filter(schema::series::title.like(val));
+    };
+
+    // 顺序选择
+    for order in parse_order_from_string(pg.order_by).into_iter().flatten() {
+        query = match order.column.as_str() {
+            "id" => query.then_order_by_with_dir(order.direction, schema::series::id),
+            "title" => query.then_order_by_with_dir(order.direction, schema::series::title),
+            "created_at" => {
+                query.then_order_by_with_dir(order.direction, schema::series::created_at)
+            }
+            _ => query,
+        }
+    }
+
+    let results = query
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<Series>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = query_count
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(ListResponse::new(results).count(count)))
+}
+
+#[get("/item/<id>")]
+async fn get_series(db: &State<db::Pool>, id: i32) -> Result<Json<SeriesFull>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let series = schema::series::table
+        .find(id)
+        .first::<Series>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let volumes = schema::series_novels::table
+        .inner_join(schema::novels::table)
+        .filter(schema::series_novels::series_id.eq(id))
+        .order(schema::series_novels::position.asc())
+        .select((
+            schema::series_novels::novel_id,
+            schema::novels::title,
+            schema::series_novels::position,
+        ))
+        .load::<(i32, String, i32)>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .into_iter()
+        .map(|(novel_id, title, position)| SeriesVolume {
+            novel_id,
+            title,
+            position,
+        })
+        .collect();
+
+    let description_html = match &series.description {
+        Some(description) => Some(
+            render_markdown_cached(&mut conn, description)
+                .await
+                .map_err(|_| Status::InternalServerError)?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(SeriesFull {
+        series,
+        volumes,
+        description_html,
+    }))
+}
+
+#[derive(Deserialize)]
+struct NewSeriesForm {
+    title: String,
+    description: Option<String>,
+    /// 按阅读顺序排列的小说id
+    novel_ids: Option<Vec<i32>>,
+}
+
+/// 按给定顺序替换系列中的小说，小说已属于其他系列时会被移动到该系列
+async fn replace_volumes(
+    conn: &mut AsyncPgConnection,
+    series_id: i32,
+    novel_ids: &[i32],
+) -> Result<(), diesel::result::Error> {
+    delete(schema::series_novels::table)
+        .filter(
+            schema::series_novels::series_id
+                .eq(series_id)
+                .or(schema::series_novels::novel_id.eq_any(novel_ids)),
+        )
+        .execute(conn)
+        .await?;
+
+    if novel_ids.is_empty() {
+        return Ok(());
+    }
+
+    insert_into(schema::series_novels::table)
+        .values(
+            novel_ids
+                .iter()
+                .enumerate()
+                .map(|(index, novel_id)| {
+                    (
+                        schema::series_novels::series_id.eq(series_id),
+                        schema::series_novels::novel_id.eq(novel_id),
+                        schema::series_novels::position.eq(index as i32 + 1),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+fn series_error_to_status(err: diesel::result::Error) -> Status {
+    match err {
+        diesel::result::Error::NotFound => Status::NotFound,
+        diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation | DatabaseErrorKind::UniqueViolation,
+            _,
+        ) => Status::UnprocessableEntity,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[post("/item", data = "<data>")]
+async fn create_series(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    data: Json<NewSeriesForm>,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let series_id = conn
+        .transaction::<i32, diesel::result::Error, _>(|conn| {
+            async move {
+                let series_id = insert_into(schema::series::table)
+                    .values((
+                        schema::series::title.eq(&data.title),
+                        schema::series::description.eq(&data.description),
+                    ))
+                    .returning(schema::series::id)
+                    .get_result::<i32>(conn)
+                    .await?;
+
+                if let Some(novel_ids) = &data.novel_ids {
+                    replace_volumes(conn, series_id, novel_ids).await?;
+                }
+
+                Ok(series_id)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(series_error_to_status)?;
+
+    info!("Create series: {}", series_id);
+
+    Ok(Json(InsertResponse { id: series_id }))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct UpdateSeriesForm {
+    title: Option<String>,
+    description: Option<String>,
+    novel_ids: Option<Vec<i32>>,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = schema::series)]
+struct SeriesForUpdate {
+    title: Option<String>,
+    description: Option<String>,
+}
+
+impl SeriesForUpdate {
+    fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none()
+    }
+}
+
+#[put("/item/<id>", data = "<data>")]
+async fn update_series(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<UpdateSeriesForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    schema::series::table
+        .find(id)
+        .first::<Series>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let data = data.into_inner();
+    let update_data = SeriesForUpdate {
+        title: data.title,
+        description: data.description,
+    };
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            if !update_data.is_empty() {
+                update(schema::series::table)
+                    .filter(schema::series::id.eq(id))
+                    .set(update_data)
+                    .execute(conn)
+                    .await?;
+            }
+
+            if let Some(novel_ids) = data.novel_ids {
+                replace_volumes(conn, id, &novel_ids).await?;
+            }
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(series_error_to_status)?;
+
+    info!("Update series: {}", id);
+
+    Ok(Json(UpdateResponse { id }))
+}
+
+#[delete("/item/<id>")]
+async fn delete_series(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+) -> Result<Json<DeleteResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    schema::series::table
+        .find(id)
+        .first::<Series>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    delete(schema::series::table.filter(schema::series::id.eq(id)))
+        .execute(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    info!("Delete series: {}", id);
+
+    Ok(Json(DeleteResponse { id }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        list_series,
+        get_series,
+        create_series,
+        update_series,
+        delete_series
+    ]
+}
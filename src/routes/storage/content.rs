@@ -4,12 +4,12 @@ use aws_sdk_s3::{
 };
 use diesel::{delete, insert_into, ExpressionMethods, QueryDsl};
 use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
-use log::info;
+use log::{info, warn};
 use md5;
 use rocket::{
     fs::TempFile,
     // get,
-    http::{ContentType, Status},
+    http::Status,
     post,
     serde::json::Json,
     tokio::io,
@@ -22,6 +22,8 @@ use crate::{
     db,
     misc::enums::SiteContentKind,
     models::*,
+    novel_format::{detect_novel_format, novel_format_error_to_status},
+    novel_metadata::extract_novel_metadata,
     schema,
     utils::{
         response::{DeleteResponse, InsertResponse},
@@ -42,19 +44,20 @@ pub async fn create_novel_object(
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let binary = ContentType::Binary;
-    let content_type = file.content_type().or(Some(&binary)).unwrap();
-
-    if content_type.ne(&ContentType::PDF) {
-        return Err(Status::UnprocessableEntity);
-    };
-
     let mut data_stream = file.open().await.map_err(|_| Status::BadRequest)?;
     let mut data_vec: Vec<u8> = vec![];
     io::copy(&mut data_stream, &mut data_vec)
         .await
         .map_err(|_| Status::InternalServerError)?;
 
+    let raw_name = file
+        .raw_name()
+        .map(|v| v.dangerous_unsafe_unsanitized_raw().as_str());
+
+    let detected = detect_novel_format(data_vec, file.content_type(), raw_name)
+        .map_err(novel_format_error_to_status)?;
+    let data_vec = detected.data;
+
     let digest = md5::compute(&data_vec);
     let md5 = format!("{:x}", digest);
 
@@ -71,25 +74,35 @@ pub async fn create_novel_object(
         }));
     };
 
+    // 元数据提取失败不影响上传
+    let format = detected.format;
+    let (data_vec, metadata) = tokio::task::spawn_blocking(move || {
+        let metadata = extract_novel_metadata(format, &data_vec);
+        (data_vec, metadata)
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    let metadata = match metadata.and_then(|v| Ok(serde_json::to_value(v)?)) {
+        Ok(v) => Some(v),
+        Err(err) => {
+            warn!("Failed to extract novel metadata: {:#}", err);
+            None
+        }
+    };
+
     let filename = format!(
         "{}.{}",
         Uuid::new_v4().simple().to_string(),
-        content_type.extension().unwrap().as_str()
+        detected.format.extension()
     );
 
     let key = format!("{}{}", NOVEL_PREFIX, filename);
 
     let inserted_id = conn
         .transaction::<i32, TransactionError<PutObjectError>, _>(|conn| {
-            /*
-            let content_type = file
-                .content_type()
-                .or(Some(&ContentType::Binary))
-                .unwrap()
-                .to_owned();
-            */
-            let new_content_type = ContentType::PDF;
-            let length = file.len().to_owned();
+            let new_content_type = detected.format.content_type();
+            let length = data_vec.len();
             async move {
                 let id = insert_into(schema::site_storage::table)
                     .values((
@@ -99,6 +112,7 @@ pub async fn create_novel_object(
                         schema::site_storage::hash.eq(&md5),
                         schema::site_storage::kind.eq(SiteContentKind::Novel as i16),
                         schema::site_storage::mime_type.eq(&new_content_type.to_string()),
+                        schema::site_storage::metadata.eq(&metadata),
                     ))
                     .returning(schema::site_storage::id)
                     .get_result::<i32>(conn)
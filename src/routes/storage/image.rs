@@ -1,15 +1,9 @@
-use std::io::Cursor;
-
-use aws_sdk_s3::{
-    operation::{delete_object::DeleteObjectError, put_object::PutObjectError},
-    primitives::ByteStream,
+use aws_sdk_s3::{operation::delete_object::DeleteObjectError, primitives::ByteStream};
+use diesel::{
+    delete, BoolExpressionMethods, ExpressionMethods, PgArrayExpressionMethods, QueryDsl,
 };
-use diesel::{delete, insert_into, ExpressionMethods, QueryDsl};
 use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
-use image::io::Reader as ImageReader;
-use image::ImageOutputFormat;
-use log::info;
-use md5;
+use log::{error, info};
 use rocket::{
     delete,
     form::Form,
@@ -21,19 +15,29 @@ use rocket::{
     tokio::io,
     Route, State,
 };
+use serde::Serialize;
+use uuid::Uuid;
 
 use crate::{
     db,
+    image_pipeline::{
+        fetch_image, image_process_error_to_status, import_web_images, prepare_image_blocking,
+        prepare_pending_image, store_fetched_images, store_image_batch, store_pending_images,
+        ImageErrorDetail, PendingImage, WebImportResult,
+    },
     models::*,
     schema,
+    task_queue::{delete_uploads, PendingUpload, TaskPayload, TaskQueue},
     utils::{
         response::{DeleteResponse, InsertResponse},
-        result_error_to_status, transaction_error_to_status, ApiTokenClaims, TransactionError,
+        result_error_to_status, sdk_error_to_status, transaction_error_to_status, ApiTokenClaims,
+        TransactionError,
     },
+    web_fetch::fetch_error_to_status,
     AppState, BUCKET,
 };
 
-const IMAGE_PREFIX: &'static str = "image/";
+const UPLOAD_PREFIX: &str = "upload/";
 
 impl<T> From<diesel::result::Error> for TransactionError<T> {
     fn from(value: diesel::result::Error) -> Self {
@@ -41,18 +45,9 @@ impl<T> From<diesel::result::Error> for TransactionError<T> {
     }
 }
 
-#[post("/item", data = "<file>")]
-async fn create_object(
-    app_state: &State<AppState>,
-    auth: Option<ApiTokenClaims>,
-    db: &State<db::Pool>,
-    file: TempFile<'_>,
-) -> Result<Json<InsertResponse<String>>, Status> {
-    auth.ok_or(Status::Forbidden)?;
-    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
-
+async fn read_image_file(file: &TempFile<'_>) -> Result<Vec<u8>, Status> {
     let binary = ContentType::Binary;
-    let content_type = file.content_type().or(Some(&binary)).unwrap();
+    let content_type = file.content_type().unwrap_or(&binary);
 
     if content_type.top().ne("image") {
         return Err(Status::UnprocessableEntity);
@@ -64,85 +59,37 @@ async fn create_object(
         .await
         .map_err(|_| Status::InternalServerError)?;
 
-    let mut new_data_vec: Vec<u8> = Vec::new();
-    ImageReader::new(Cursor::new(data_vec))
-        .with_guessed_format()
-        .map_err(|_| Status::InternalServerError)?
-        .decode()
-        .map_err(|_| Status::BadRequest)?
-        .write_to(&mut Cursor::new(&mut new_data_vec), ImageOutputFormat::WebP)
-        .map_err(|_| Status::InternalServerError)?;
+    Ok(data_vec)
+}
 
-    let digest = md5::compute(&new_data_vec);
-    let md5 = format!("{:x}", digest);
+#[post("/item", data = "<file>")]
+async fn create_object(
+    app_state: &State<AppState>,
+    auth: Option<ApiTokenClaims>,
+    db: &State<db::Pool>,
+    file: TempFile<'_>,
+) -> Result<Json<InsertResponse<String>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let new_content_type = ContentType::WEBP;
+    let data_vec = read_image_file(&file).await?;
 
-    let objs = schema::local_files::table
-        .find(&md5)
-        .load::<LocalFile>(&mut conn)
+    let pending_image = prepare_pending_image(&mut conn, data_vec)
         .await
-        .map_err(|_| Status::InternalServerError)?;
+        .map_err(image_process_error_to_status)?;
 
-    if !objs.is_empty() {
-        return Ok(Json(InsertResponse {
-            id: objs[0].id.to_owned(),
-        }));
+    if let PendingImage::Stored(id) = pending_image {
+        return Ok(Json(InsertResponse { id }));
     }
 
-    let filename = if let Some(ext) = new_content_type.extension() {
-        format!("{}.{}", md5, ext.as_str())
-    } else {
-        md5.to_owned()
-    };
-
-    let key = format!("{}{}", IMAGE_PREFIX, filename);
-
-    let md5_ = md5.to_owned();
-
-    conn.transaction::<(), TransactionError<PutObjectError>, _>(|conn| {
-        /*
-        let content_type = file
-            .content_type()
-            .or(Some(&ContentType::Binary))
-            .unwrap()
-            .to_owned();
-        */
-        let new_content_type = ContentType::WEBP;
-        let length = new_data_vec.len();
-        async move {
-            insert_into(schema::local_files::table)
-                .values((
-                    schema::local_files::id.eq(md5),
-                    schema::local_files::file_name.eq(&filename),
-                    schema::local_files::path.eq(&key),
-                ))
-                .execute(conn)
-                .await
-                .map_err(|err| TransactionError::ResultError(err))?;
-
-            app_state
-                .s3_client
-                .put_object()
-                .body(ByteStream::from(new_data_vec))
-                .bucket(BUCKET)
-                .content_type(new_content_type.to_string())
-                .content_length(length as i64)
-                .key(&key)
-                .send()
-                .await
-                .map_err(|err| TransactionError::SdkError(err))?;
-
-            Ok(())
-        }
-        .scope_boxed()
-    })
-    .await
-    .map_err(transaction_error_to_status)?;
+    let id = store_pending_images(&mut conn, &app_state.s3_client, vec![pending_image])
+        .await
+        .map_err(transaction_error_to_status)?
+        .remove(0);
 
-    info!("Object created: {}", md5_);
+    info!("Object created: {}", id);
 
-    Ok(Json(InsertResponse { id: md5_ }))
+    Ok(Json(InsertResponse { id }))
 }
 
 #[derive(FromForm)]
@@ -151,145 +98,96 @@ struct UploadMultipleImage<'r> {
     files: Vec<TempFile<'r>>,
 }
 
-#[derive(Clone, Debug)]
-struct UploadImageData {
-    filename: String,
-    key: String,
-    data: Vec<u8>,
-    md5: String,
-    content_type: ContentType,
-}
-
-#[derive(Clone, Debug)]
-struct UploadImageItem {
-    data: Option<UploadImageData>,
-    uploaded_id: Option<String>,
+#[derive(Serialize, Debug, Clone)]
+struct UploadResult {
+    /// 上传时的原始文件名
+    file_name: Option<String>,
+    id: Option<String>,
+    /// 已存在相同的文件，或与同一批次中的其他文件重复
+    deduplicated: bool,
+    format: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    error: Option<ImageErrorDetail>,
 }
 
-#[post("/item_multi", data = "<files>")]
+/// 逐个返回每个文件的结果
+///
+/// atomic默认为true，任一文件失败时不保存任何文件并返回422，
+/// 为false时保存其余可以处理的文件
+#[post("/item_multi?<atomic>", data = "<files>")]
 async fn create_object_multi(
     app_state: &State<AppState>,
     auth: Option<ApiTokenClaims>,
     db: &State<db::Pool>,
+    atomic: Option<bool>,
     files: Form<UploadMultipleImage<'_>>,
-) -> Result<Json<InsertResponse<Vec<String>>>, Status> {
+) -> Result<(Status, Json<Vec<UploadResult>>), Status> {
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let mut pending_datas: Vec<UploadImageItem> = Vec::with_capacity(files.files.len());
+    let mut results: Vec<UploadResult> = Vec::with_capacity(files.files.len());
+    let mut prepared_images = Vec::with_capacity(files.files.len());
 
     for file in &files.files {
-        let binary = ContentType::Binary;
-        let content_type = file.content_type().or(Some(&binary)).unwrap();
-
-        if content_type.top().ne("image") {
-            return Err(Status::UnprocessableEntity);
+        let mut result = UploadResult {
+            file_name: file
+                .raw_name()
+                .map(|v| v.dangerous_unsafe_unsanitized_raw().as_str().to_owned()),
+            id: None,
+            deduplicated: false,
+            format: None,
+            width: None,
+            height: None,
+            error: None,
         };
 
-        let mut data_stream = file.open().await.map_err(|_| Status::BadRequest)?;
-        let mut data_vec: Vec<u8> = vec![];
-        io::copy(&mut data_stream, &mut data_vec)
-            .await
-            .map_err(|_| Status::InternalServerError)?;
-
-        let mut new_data_vec: Vec<u8> = Vec::new();
-        ImageReader::new(Cursor::new(data_vec))
-            .with_guessed_format()
-            .map_err(|_| Status::InternalServerError)?
-            .decode()
-            .map_err(|_| Status::BadRequest)?
-            .write_to(&mut Cursor::new(&mut new_data_vec), ImageOutputFormat::WebP)
-            .map_err(|_| Status::InternalServerError)?;
-
-        let digest = md5::compute(&new_data_vec);
-        let md5 = format!("{:x}", digest);
-
-        let new_content_type = ContentType::WEBP;
+        let prepared = match read_image_file(file).await {
+            Ok(data_vec) => prepare_image_blocking(data_vec)
+                .await
+                .map_err(ImageErrorDetail::from),
+            Err(status) => Err(ImageErrorDetail::from(status)),
+        };
 
-        let objs = schema::local_files::table
-            .find(&md5)
-            .load::<LocalFile>(&mut conn)
-            .await
-            .map_err(|_| Status::InternalServerError)?;
-
-        if !objs.is_empty() {
-            pending_datas.push(UploadImageItem {
-                data: None,
-                uploaded_id: Some(objs[0].id.to_owned()),
-            });
-            continue;
+        if let Ok(prepared) = &prepared {
+            result.format = prepared.source_format.to_owned();
+            result.width = Some(prepared.width);
+            result.height = Some(prepared.height);
         }
 
-        let filename = if let Some(ext) = new_content_type.extension() {
-            format!("{}.{}", md5, ext.as_str())
-        } else {
-            md5.to_owned()
-        };
+        results.push(result);
+        prepared_images.push(prepared.map(|v| (v, None)));
+    }
 
-        let key = format!("{}{}", IMAGE_PREFIX, filename);
-
-        pending_datas.push(UploadImageItem {
-            data: Some(UploadImageData {
-                filename,
-                key,
-                data: new_data_vec,
-                md5,
-                content_type: new_content_type,
-            }),
-            uploaded_id: None,
-        })
+    let stored = store_image_batch(
+        &mut conn,
+        &app_state.s3_client,
+        prepared_images,
+        atomic.unwrap_or(true),
+    )
+    .await
+    .map_err(transaction_error_to_status)?;
+
+    for (result, stored) in results.iter_mut().zip(stored) {
+        result.id = stored.id;
+        result.deduplicated = stored.deduplicated;
+        result.error = stored.error;
     }
 
-    let uploaded_ids = conn
-        .transaction::<Vec<String>, TransactionError<PutObjectError>, _>(|conn| {
-            async move {
-                let mut uploaded_ids: Vec<String> = Vec::with_capacity(pending_datas.len());
-                let mut values: Vec<_> = Vec::with_capacity(pending_datas.len());
-                for item in &pending_datas {
-                    if let Some(data) = &item.data {
-                        values.push((
-                            schema::local_files::id.eq(&data.md5),
-                            schema::local_files::file_name.eq(&data.filename),
-                            schema::local_files::path.eq(&data.key),
-                        ));
-                        uploaded_ids.push(data.md5.to_owned());
-                    } else {
-                        uploaded_ids.push(item.uploaded_id.as_ref().unwrap().to_owned());
-                    }
-                }
-
-                insert_into(schema::local_files::table)
-                    .values(values)
-                    .execute(conn)
-                    .await
-                    .map_err(|err| TransactionError::ResultError(err))?;
-
-                for item in &pending_datas {
-                    if let Some(data) = &item.data {
-                        app_state
-                            .s3_client
-                            .put_object()
-                            .body(ByteStream::from(data.data.to_owned()))
-                            .bucket(BUCKET)
-                            .content_type(data.content_type.to_string())
-                            .content_length(data.data.len() as i64)
-                            .key(&data.key)
-                            .send()
-                            .await
-                            .map_err(|err| TransactionError::SdkError(err))?;
-                    }
-                }
-
-                Ok(uploaded_ids)
-            }
-            .scope_boxed()
-        })
-        .await
-        .map_err(transaction_error_to_status)?;
+    if results.iter().any(|v| v.error.is_some()) && atomic.unwrap_or(true) {
+        return Ok((Status::UnprocessableEntity, Json(results)));
+    }
 
-    info!("Objects created:\n{}", uploaded_ids.join("\n"));
+    info!(
+        "Objects created:\n{}",
+        results
+            .iter()
+            .filter_map(|v| v.id.to_owned())
+            .collect::<Vec<String>>()
+            .join("\n")
+    );
 
-    Ok(Json(InsertResponse { id: uploaded_ids }))
+    Ok((Status::Ok, Json(results)))
 }
 
 #[post("/item_from_web", data = "<url>")]
@@ -302,260 +200,214 @@ async fn create_object_from_web(
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let resp_head = app_state.reqwest_client
-        .head(&url)
-        .send()
+    let fetched = fetch_image(&app_state.web_fetcher, &url)
         .await
-        .map_err(|_| Status::BadRequest)?
-        .error_for_status()
-        .map_err(|_| Status::BadRequest)?;
-
-    let content_type_str = if let Some(v) = resp_head.headers().get("content-type") {
-        v.to_str().map_or("application/octet-stream", |v| v)
-    } else {
-        "application/octet-stream"
-    };
-
-    let content_type = ContentType::parse_flexible(content_type_str)
-        .or(Some(ContentType::Binary))
-        .unwrap();
+        .map_err(fetch_error_to_status)?;
 
-    if content_type.top().ne("image") {
-        return Err(Status::UnprocessableEntity);
-    };
-
-    let resp = app_state.reqwest_client
-        .get(url)
-        .send()
+    let pending_image = prepare_pending_image(&mut conn, fetched.data)
         .await
-        .map_err(|_| Status::BadRequest)?
-        .error_for_status()
-        .map_err(|_| Status::BadRequest)?;
-
-    let resp_data = resp.bytes().await.map_err(|_| Status::BadRequest)?;
-
-    let mut new_data_vec: Vec<u8> = Vec::new();
-    ImageReader::new(Cursor::new(resp_data))
-        .with_guessed_format()
-        .map_err(|_| Status::InternalServerError)?
-        .decode()
-        .map_err(|_| Status::BadRequest)?
-        .write_to(&mut Cursor::new(&mut new_data_vec), ImageOutputFormat::WebP)
-        .map_err(|_| Status::InternalServerError)?;
-
-    let digest = md5::compute(&new_data_vec);
-    let md5 = format!("{:x}", digest);
-
-    let new_content_type = ContentType::WEBP;
-
-    let objs = schema::local_files::table
-        .find(&md5)
-        .load::<LocalFile>(&mut conn)
-        .await
-        .map_err(|_| Status::InternalServerError)?;
+        .map_err(image_process_error_to_status)?;
 
-    if !objs.is_empty() {
-        return Ok(Json(InsertResponse {
-            id: objs[0].id.to_owned(),
-        }));
-    }
-
-    let filename = if let Some(ext) = new_content_type.extension() {
-        format!("{}.{}", md5, ext.as_str())
-    } else {
-        md5.to_owned()
-    };
+    let id = store_fetched_images(
+        &mut conn,
+        &app_state.s3_client,
+        vec![(pending_image, fetched.source)],
+    )
+    .await
+    .map_err(transaction_error_to_status)?
+    .remove(0);
 
-    let key = format!("{}{}", IMAGE_PREFIX, filename);
+    info!("Object created: {}", id);
 
-    let md5_ = md5.to_owned();
+    Ok(Json(InsertResponse { id }))
+}
 
-    conn.transaction::<(), TransactionError<PutObjectError>, _>(|conn| {
-        let length = new_data_vec.len();
-        async move {
-            insert_into(schema::local_files::table)
-                .values((
-                    schema::local_files::id.eq(md5),
-                    schema::local_files::file_name.eq(&filename),
-                    schema::local_files::path.eq(&key),
-                ))
-                .execute(conn)
-                .await
-                .map_err(|err| TransactionError::ResultError(err))?;
+fn split_urls(urls: &str) -> Vec<String> {
+    urls.split(',')
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<String>>()
+}
 
-            app_state
-                .s3_client
-                .put_object()
-                .body(ByteStream::from(new_data_vec))
-                .bucket(BUCKET)
-                .content_type(new_content_type.to_string())
-                .content_length(length as i64)
-                .key(&key)
-                .send()
-                .await
-                .map_err(|err| TransactionError::SdkError(err))?;
+/// 逐个返回每个地址的结果，部分地址失败时其余地址仍会保存
+#[post("/item_from_web_multi", data = "<urls>")]
+async fn create_object_from_web_multi(
+    app_state: &State<AppState>,
+    auth: Option<ApiTokenClaims>,
+    db: &State<db::Pool>,
+    urls: String,
+) -> Result<Json<Vec<WebImportResult>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-            Ok(())
-        }
-        .scope_boxed()
-    })
+    let results = import_web_images(
+        &mut conn,
+        &app_state.s3_client,
+        &app_state.web_fetcher,
+        split_urls(&urls),
+    )
     .await
     .map_err(transaction_error_to_status)?;
 
-    info!("Object created: {}", md5_);
+    info!(
+        "Objects created:\n{}",
+        results
+            .iter()
+            .filter_map(|v| v.id.to_owned())
+            .collect::<Vec<String>>()
+            .join("\n")
+    );
 
-    Ok(Json(InsertResponse { id: md5_ }))
+    Ok(Json(results))
 }
 
-#[post("/item_from_web_multi", data = "<urls>")]
-async fn create_object_from_web_multi(
+#[post("/task/item_multi", data = "<files>")]
+async fn create_object_multi_task(
     app_state: &State<AppState>,
     auth: Option<ApiTokenClaims>,
     db: &State<db::Pool>,
-    urls: String,
-) -> Result<Json<InsertResponse<Vec<String>>>, Status> {
+    task_queue: &State<TaskQueue>,
+    files: Form<UploadMultipleImage<'_>>,
+) -> Result<Json<InsertResponse<i32>>, Status> {
     auth.ok_or(Status::Forbidden)?;
     let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-    let urls_vec = urls
-        .split(",")
-        .map(|v| v.trim().to_owned())
-        .filter(|v| !v.is_empty())
-        .collect::<Vec<String>>();
+    // 先读取并检查全部文件，避免部分文件上传后请求失败
+    let mut files_data: Vec<(Vec<u8>, Option<String>)> = Vec::with_capacity(files.files.len());
+    for file in &files.files {
+        files_data.push((
+            read_image_file(file).await?,
+            // 与同步上传一致，记录客户端提供的原始文件名
+            file.raw_name()
+                .map(|v| v.dangerous_unsafe_unsanitized_raw().as_str().to_owned()),
+        ));
+    }
 
-    let mut pending_datas: Vec<UploadImageItem> = Vec::with_capacity(urls_vec.len());
+    let mut uploads: Vec<PendingUpload> = Vec::with_capacity(files_data.len());
 
-    for url in urls_vec {
-        let resp_head = app_state.reqwest_client
-            .head(&url)
+    for (data_vec, file_name) in files_data {
+        let key = format!("{}{}", UPLOAD_PREFIX, Uuid::new_v4().simple());
+        let length = data_vec.len();
+
+        if let Err(err) = app_state
+            .s3_client
+            .put_object()
+            .body(ByteStream::from(data_vec))
+            .bucket(BUCKET)
+            .content_length(length as i64)
+            .key(&key)
             .send()
             .await
-            .map_err(|_| Status::BadRequest)?
-            .error_for_status()
-            .map_err(|_| Status::BadRequest)?;
-
-        let content_type_str = if let Some(v) = resp_head.headers().get("content-type") {
-            v.to_str().map_or("application/octet-stream", |v| v)
-        } else {
-            "application/octet-stream"
-        };
+        {
+            delete_uploads(&app_state.s3_client, &uploads).await;
+            return Err(sdk_error_to_status(err));
+        }
 
-        let content_type = ContentType::parse_flexible(content_type_str)
-            .or(Some(ContentType::Binary))
-            .unwrap();
+        uploads.push(PendingUpload { key, file_name });
+    }
 
-        if content_type.top().ne("image") {
-            return Err(Status::UnprocessableEntity);
-        };
+    let task_id = match task_queue
+        .enqueue(&mut conn, TaskPayload::ImageUpload { uploads: uploads.to_owned() })
+        .await
+    {
+        Ok(task_id) => task_id,
+        Err(err) => {
+            error!("Failed to enqueue upload task: {:?}", err);
+            delete_uploads(&app_state.s3_client, &uploads).await;
+            return Err(Status::InternalServerError);
+        }
+    };
 
-        let resp = app_state.reqwest_client
-            .get(url)
-            .send()
-            .await
-            .map_err(|_| Status::BadRequest)?
-            .error_for_status()
-            .map_err(|_| Status::BadRequest)?;
+    info!("Upload task created: {}", task_id);
 
-        let resp_data = resp.bytes().await.map_err(|_| Status::BadRequest)?;
+    Ok(Json(InsertResponse { id: task_id }))
+}
 
-        let mut new_data_vec: Vec<u8> = Vec::new();
-        ImageReader::new(Cursor::new(resp_data))
-            .with_guessed_format()
-            .map_err(|_| Status::InternalServerError)?
-            .decode()
-            .map_err(|_| Status::BadRequest)?
-            .write_to(&mut Cursor::new(&mut new_data_vec), ImageOutputFormat::WebP)
-            .map_err(|_| Status::InternalServerError)?;
+#[post("/task/item_from_web_multi", data = "<urls>")]
+async fn create_object_from_web_multi_task(
+    auth: Option<ApiTokenClaims>,
+    db: &State<db::Pool>,
+    task_queue: &State<TaskQueue>,
+    urls: String,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
 
-        let digest = md5::compute(&new_data_vec);
-        let md5 = format!("{:x}", digest);
+    let urls_vec = split_urls(&urls);
 
-        let new_content_type = ContentType::WEBP;
+    if urls_vec.is_empty() {
+        return Err(Status::UnprocessableEntity);
+    }
 
-        let objs = schema::local_files::table
-            .find(&md5)
-            .load::<LocalFile>(&mut conn)
-            .await
-            .map_err(|_| Status::InternalServerError)?;
-
-        if !objs.is_empty() {
-            pending_datas.push(UploadImageItem {
-                data: None,
-                uploaded_id: Some(objs[0].id.to_owned()),
-            });
-            continue;
-        }
+    let task_id = task_queue
+        .enqueue(&mut conn, TaskPayload::ImageFromWeb { urls: urls_vec })
+        .await
+        .map_err(|_| Status::InternalServerError)?;
 
-        let filename = if let Some(ext) = new_content_type.extension() {
-            format!("{}.{}", md5, ext.as_str())
-        } else {
-            md5.to_owned()
-        };
+    info!("Web image task created: {}", task_id);
 
-        let key = format!("{}{}", IMAGE_PREFIX, filename);
-
-        pending_datas.push(UploadImageItem {
-            data: Some(UploadImageData {
-                filename,
-                key,
-                data: new_data_vec,
-                md5,
-                content_type: new_content_type,
-            }),
-            uploaded_id: None,
-        })
-    }
+    Ok(Json(InsertResponse { id: task_id }))
+}
 
-    let uploaded_ids = conn
-        .transaction::<Vec<String>, TransactionError<PutObjectError>, _>(|conn| {
-            async move {
-                let mut uploaded_ids: Vec<String> = Vec::with_capacity(pending_datas.len());
-                let mut values: Vec<_> = Vec::with_capacity(pending_datas.len());
-                for item in &pending_datas {
-                    if let Some(data) = &item.data {
-                        values.push((
-                            schema::local_files::id.eq(&data.md5),
-                            schema::local_files::file_name.eq(&data.filename),
-                            schema::local_files::path.eq(&data.key),
-                        ));
-                        uploaded_ids.push(data.md5.to_owned());
-                    } else {
-                        uploaded_ids.push(item.uploaded_id.as_ref().unwrap().to_owned());
-                    }
-                }
-
-                insert_into(schema::local_files::table)
-                    .values(values)
-                    .execute(conn)
-                    .await
-                    .map_err(|err| TransactionError::ResultError(err))?;
-
-                for item in &pending_datas {
-                    if let Some(data) = &item.data {
-                        app_state
-                            .s3_client
-                            .put_object()
-                            .body(ByteStream::from(data.data.to_owned()))
-                            .bucket(BUCKET)
-                            .content_type(data.content_type.to_string())
-                            .content_length(data.data.len() as i64)
-                            .key(&data.key)
-                            .send()
-                            .await
-                            .map_err(|err| TransactionError::SdkError(err))?;
-                    }
-                }
-
-                Ok(uploaded_ids)
-            }
-            .scope_boxed()
-        })
+#[derive(Serialize, Debug)]
+struct SourceLookup {
+    imported: bool,
+    local_files: Vec<LocalFile>,
+    /// urls中包含该地址或使用了以上文件的图片条目
+    image_item_ids: Vec<i32>,
+}
+
+/// 查询地址是否已经导入过，地址与请求地址或重定向后的地址相同即视为已导入
+#[get("/source?<url>")]
+async fn lookup_source(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    url: &str,
+) -> Result<Json<SourceLookup>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let url = url.trim();
+
+    let local_files = schema::local_files::table
+        .filter(
+            schema::local_files::source_url
+                .eq(url)
+                .or(schema::local_files::source_final_url.eq(url)),
+        )
+        .order(schema::local_files::created_at.asc())
+        .load::<LocalFile>(&mut conn)
         .await
-        .map_err(transaction_error_to_status)?;
+        .map_err(|_| Status::InternalServerError)?;
 
-    info!("Objects created:\n{}", uploaded_ids.join("\n"));
+    let local_file_ids = local_files
+        .iter()
+        .map(|v| v.id.to_owned())
+        .collect::<Vec<String>>();
+
+    let image_item_ids = schema::image_items::table
+        .filter(
+            schema::image_items::urls
+                .contains(vec![url])
+                .or(schema::image_items::id.eq_any(
+                    schema::image_items_local_files::table
+                        .filter(
+                            schema::image_items_local_files::local_file_id.eq_any(&local_file_ids),
+                        )
+                        .select(schema::image_items_local_files::image_item_id),
+                )),
+        )
+        .select(schema::image_items::id)
+        .order(schema::image_items::id.asc())
+        .load::<i32>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
 
-    Ok(Json(InsertResponse { id: uploaded_ids }))
+    Ok(Json(SourceLookup {
+        imported: !local_files.is_empty() || !image_item_ids.is_empty(),
+        local_files,
+        image_item_ids,
+    }))
 }
 
 #[get("/item/<id>")]
@@ -624,6 +476,9 @@ pub fn routes() -> Vec<Route> {
         create_object_multi,
         create_object_from_web,
         create_object_from_web_multi,
+        create_object_multi_task,
+        create_object_from_web_multi_task,
+        lookup_source,
         get_object,
         delete_object
     ]
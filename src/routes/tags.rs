@@ -0,0 +1,823 @@
+use crate::{
+    db,
+    misc::enums::TagNamespace,
+    models::*,
+    schema,
+    utils::{
+        parse_order_from_string, require_admin,
+        response::{DeleteResponse, InsertResponse, ListResponse, UpdateResponse},
+        result_error_to_status, ApiTokenClaims, Pagination,
+    },
+};
+use diesel::{
+    delete, insert_into,
+    result::DatabaseErrorKind,
+    sql_query,
+    sql_types::{BigInt, Integer, Nullable, Text},
+    update, BoolExpressionMethods, ExpressionMethods, JoinOnDsl, OptionalExtension, QueryDsl,
+    QueryableByName, TextExpressionMethods,
+};
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use diesel_order_with_direction::OrderWithDirectionDsl;
+use log::info;
+use rocket::{delete, get, http::Status, post, put, serde::json::Json, Route, State};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 带有前缀的namespace，与tags.key的生成规则保持一致
+const NAMESPACE_PREFIXES: [(i16, &str); 3] = [
+    (TagNamespace::Genre as i16, "genre"),
+    (TagNamespace::Character as i16, "character"),
+    (TagNamespace::Language as i16, "language"),
+];
+
+fn is_valid_namespace(namespace: i16) -> bool {
+    namespace == TagNamespace::General as i16
+        || NAMESPACE_PREFIXES.iter().any(|(v, _)| *v == namespace)
+}
+
+/// 将`genre:fantasy`形式的字符串拆分为namespace和名称，未知前缀视为名称的一部分
+fn parse_tag(value: &str) -> (i16, String) {
+    if let Some((prefix, name)) = value.split_once(':') {
+        if let Some((namespace, _)) = NAMESPACE_PREFIXES.iter().find(|(_, v)| *v == prefix) {
+            return (*namespace, name.trim().to_owned());
+        }
+    }
+
+    (TagNamespace::General as i16, value.to_owned())
+}
+
+fn tag_key(namespace: i16, name: String) -> String {
+    match NAMESPACE_PREFIXES.iter().find(|(v, _)| *v == namespace) {
+        Some((_, prefix)) => format!("{}:{}", prefix, name),
+        None => name,
+    }
+}
+
+/// 将输入的标签统一为规范形式：别名替换为目标标签，并按需创建未知标签、展开隐含标签
+async fn canonicalize_tags(
+    conn: &mut AsyncPgConnection,
+    tags: &[String],
+    create_missing: bool,
+    expand_implications: bool,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let parsed = tags
+        .iter()
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(parse_tag)
+        .filter(|(_, name)| !name.is_empty())
+        .collect::<Vec<(i16, String)>>();
+
+    if parsed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if create_missing {
+        insert_into(schema::tags::table)
+            .values(
+                parsed
+                    .iter()
+                    .map(|(namespace, name)| {
+                        (
+                            schema::tags::namespace.eq(*namespace),
+                            schema::tags::name.eq(name),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+    }
+
+    let mut known = HashMap::new();
+    for (namespace, name) in &parsed {
+        if let Some(tag) = schema::tags::table
+            .filter(schema::tags::namespace.eq(namespace))
+            .filter(schema::tags::name.eq(name))
+            .first::<Tag>(conn)
+            .await
+            .optional()?
+        {
+            known.insert((*namespace, name.to_owned()), tag);
+        }
+    }
+
+    let alias_targets = schema::tags::table
+        .filter(
+            schema::tags::id.eq_any(
+                known
+                    .values()
+                    .filter_map(|v| v.alias_of)
+                    .collect::<Vec<i32>>(),
+            ),
+        )
+        .load::<Tag>(conn)
+        .await?
+        .into_iter()
+        .map(|v| (v.id, v))
+        .collect::<HashMap<i32, Tag>>();
+
+    let mut results = Vec::new();
+    let mut canonical_ids = Vec::new();
+
+    for (namespace, name) in parsed {
+        let tag = known.get(&(namespace, name.to_owned())).map(|v| {
+            v.alias_of
+                .and_then(|id| alias_targets.get(&id))
+                .unwrap_or(v)
+        });
+
+        let key = match tag {
+            Some(tag) => {
+                canonical_ids.push(tag.id);
+                tag.key.to_owned()
+            }
+            // 未登记的标签保持原样，用于筛选时不会匹配到其他标签
+            None => tag_key(namespace, name),
+        };
+
+        if !results.contains(&key) {
+            results.push(key);
+        }
+    }
+
+    if expand_implications {
+        for key in implied_tag_keys(conn, &canonical_ids).await? {
+            if !results.contains(&key) {
+                results.push(key);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// 递归读取给定标签隐含的全部标签
+async fn implied_tag_keys(
+    conn: &mut AsyncPgConnection,
+    tag_ids: &[i32],
+) -> Result<Vec<String>, diesel::result::Error> {
+    let mut visited = tag_ids.iter().copied().collect::<HashSet<i32>>();
+    let mut frontier = tag_ids.to_vec();
+    let mut keys = Vec::new();
+
+    while !frontier.is_empty() {
+        let implied = schema::tag_implications::table
+            .inner_join(
+                schema::tags::table
+                    .on(schema::tags::id.eq(schema::tag_implications::implied_tag_id)),
+            )
+            .filter(schema::tag_implications::tag_id.eq_any(&frontier))
+            .select((schema::tags::id, schema::tags::key))
+            .load::<(i32, String)>(conn)
+            .await?;
+
+        frontier = Vec::new();
+
+        for (id, key) in implied {
+            if visited.insert(id) {
+                frontier.push(id);
+                keys.push(key);
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+/// 拆分以逗号分隔的标签筛选参数
+pub fn split_tags(value: &str) -> Vec<String> {
+    value
+        .trim()
+        .split(',')
+        .map(|v| v.trim().to_owned())
+        .collect()
+}
+
+/// 写入小说或图片前规范化标签，未登记的标签会被创建
+pub async fn normalize_tags(
+    conn: &mut AsyncPgConnection,
+    tags: &[String],
+) -> Result<Vec<String>, diesel::result::Error> {
+    canonicalize_tags(conn, tags, true, true).await
+}
+
+/// 筛选时将别名解析为目标标签
+pub async fn resolve_tags(
+    conn: &mut AsyncPgConnection,
+    tags: &[String],
+) -> Result<Vec<String>, diesel::result::Error> {
+    canonicalize_tags(conn, tags, false, false).await
+}
+
+/// 使用标签的表，均带有`tags TEXT[]`列
+const TAGGED_TABLES: [&str; 2] = ["novels", "image_items"];
+
+/// 逐个解析标签查询中的别名，返回原标签到规范标签的映射
+pub async fn resolve_tag_map(
+    conn: &mut AsyncPgConnection,
+    tags: Vec<String>,
+) -> Result<HashMap<String, String>, diesel::result::Error> {
+    let mut resolved = HashMap::new();
+
+    for tag in tags {
+        if resolved.contains_key(&tag) {
+            continue;
+        }
+
+        if let Some(canonical) = resolve_tags(conn, &[tag.to_owned()])
+            .await?
+            .into_iter()
+            .next()
+        {
+            resolved.insert(tag, canonical);
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// 改写所有小说和图片中的标签，to为空时移除该标签
+async fn rewrite_tags(
+    conn: &mut AsyncPgConnection,
+    from: &str,
+    to: Option<&str>,
+) -> Result<(), diesel::result::Error> {
+    for table in TAGGED_TABLES {
+        // 已有目标标签的记录直接移除旧标签，避免产生重复
+        sql_query(format!(
+            "UPDATE {} SET tags = array_remove(tags, $1) \
+             WHERE tags @> ARRAY[$1] AND ($2 IS NULL OR tags @> ARRAY[$2])",
+            table
+        ))
+        .bind::<Text, _>(from)
+        .bind::<Nullable<Text>, _>(to)
+        .execute(conn)
+        .await?;
+
+        if let Some(to) = to {
+            sql_query(format!(
+                "UPDATE {} SET tags = array_replace(tags, $1, $2) WHERE tags @> ARRAY[$1]",
+                table
+            ))
+            .bind::<Text, _>(from)
+            .bind::<Text, _>(to)
+            .execute(conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 重新统计标签的小说和图片数量
+async fn recount_tag(conn: &mut AsyncPgConnection, id: i32) -> Result<(), diesel::result::Error> {
+    sql_query(
+        "UPDATE tags SET \
+         novel_count = (SELECT COUNT(*) FROM novels WHERE novels.tags @> ARRAY[tags.key]), \
+         image_count = (SELECT COUNT(*) FROM image_items WHERE image_items.tags @> ARRAY[tags.key]) \
+         WHERE id = $1",
+    )
+    .bind::<Integer, _>(id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// 为带有该标签的小说和图片补充隐含标签
+async fn apply_implications(
+    conn: &mut AsyncPgConnection,
+    tag: &Tag,
+) -> Result<(), diesel::result::Error> {
+    for key in implied_tag_keys(conn, &[tag.id]).await? {
+        for table in TAGGED_TABLES {
+            sql_query(format!(
+                "UPDATE {} SET tags = array_append(tags, $2) \
+                 WHERE tags @> ARRAY[$1] AND NOT tags @> ARRAY[$2]",
+                table
+            ))
+            .bind::<Text, _>(&tag.key)
+            .bind::<Text, _>(&key)
+            .execute(conn)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 别名只能指向规范标签
+async fn canonical_tag(
+    conn: &mut AsyncPgConnection,
+    id: i32,
+) -> Result<Tag, diesel::result::Error> {
+    let tag = schema::tags::table.find(id).first::<Tag>(conn).await?;
+
+    match tag.alias_of {
+        Some(alias_of) => schema::tags::table.find(alias_of).first::<Tag>(conn).await,
+        None => Ok(tag),
+    }
+}
+
+fn tag_error_to_status(err: diesel::result::Error) -> Status {
+    match err {
+        diesel::result::Error::NotFound => Status::NotFound,
+        diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation
+            | DatabaseErrorKind::UniqueViolation
+            | DatabaseErrorKind::CheckViolation,
+            _,
+        ) => Status::UnprocessableEntity,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[get("/item?<id>&<namespace>&<name>&<alias>&<pg..>")]
+async fn list_tags(
+    db: &State<db::Pool>,
+    id: Option<i32>,
+    namespace: Option<i16>,
+    name: Option<String>,
+    alias: Option<bool>,
+    pg: Pagination,
+) -> Result<Json<ListResponse<Tag>>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let mut query = schema::tags::table.into_boxed();
+    let mut query_count = schema::tags::table.into_boxed();
+
+    // 以id筛选
+    if let Some(val) = id {
+        query = query.filter(schema::tags::id.eq(val));
+        query_count = query_count.filter(schema::tags::id.eq(val));
+    };
+
+    // 以namespace筛选
+    if let Some(val) = namespace {
+        query = query.filter(schema::tags::namespace.eq(val));
+        query_count = query_count.filter(schema::tags::namespace.eq(val));
+    };
+
+    // 以name筛选
+    if let Some(val) = name {
+        query = query.filter(schema::tags::name.like(val.to_owned()));
+        query_count = query_count.filter(schema::tags::name.like(val));
+    };
+
+    // 筛选别名或规范标签
+    if let Some(val) = alias {
+        if val {
+            query = query.filter(schema::tags::alias_of.is_not_null());
+            query_count = query_count.filter(schema::tags::alias_of.is_not_null());
+        } else {
+            query = query.filter(schema::tags::alias_of.is_null());
+            query_count = query_count.filter(schema::tags::alias_of.is_null());
+        }
+    };
+
+    // 顺序选择
+    for order in parse_order_from_string(pg.order_by).into_iter().flatten() {
+        query = match order.column.as_str() {
+            "id" => query.then_order_by_with_dir(order.direction, schema::tags::id),
+            "name" => query.then_order_by_with_dir(order.direction, schema::tags::name),
+            "novel_count" => {
+                query.then_order_by_with_dir(order.direction, schema::tags::novel_count)
+            }
+            "image_count" => {
+                query.then_order_by_with_dir(order.direction, schema::tags::image_count)
+            }
+            "created_at" => query.then_order_by_with_dir(order.direction, schema::tags::created_at),
+            _ => query,
+        }
+    }
+
+    let results = query
+        .offset(pg.offset)
+        .limit(pg.limit)
+        .load::<Tag>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let count = query_count
+        .count()
+        .get_result(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(ListResponse::new(results).count(count)))
+}
+
+#[derive(Serialize, QueryableByName)]
+pub struct TagsCount {
+    #[diesel(sql_type = Text)]
+    pub tag: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+struct TagFull {
+    #[serde(flatten)]
+    tag: Tag,
+    aliases: Vec<Tag>,
+    implies: Vec<Tag>,
+    implied_by: Vec<Tag>,
+}
+
+#[get("/item/<id>")]
+async fn get_tag(db: &State<db::Pool>, id: i32) -> Result<Json<TagFull>, Status> {
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let tag = schema::tags::table
+        .find(id)
+        .first::<Tag>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let aliases = schema::tags::table
+        .filter(schema::tags::alias_of.eq(id))
+        .order(schema::tags::name.asc())
+        .load::<Tag>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let implies = schema::tags::table
+        .filter(
+            schema::tags::id.eq_any(
+                schema::tag_implications::table
+                    .filter(schema::tag_implications::tag_id.eq(id))
+                    .select(schema::tag_implications::implied_tag_id),
+            ),
+        )
+        .order(schema::tags::name.asc())
+        .load::<Tag>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    let implied_by = schema::tags::table
+        .filter(
+            schema::tags::id.eq_any(
+                schema::tag_implications::table
+                    .filter(schema::tag_implications::implied_tag_id.eq(id))
+                    .select(schema::tag_implications::tag_id),
+            ),
+        )
+        .order(schema::tags::name.asc())
+        .load::<Tag>(&mut conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(TagFull {
+        tag,
+        aliases,
+        implies,
+        implied_by,
+    }))
+}
+
+#[derive(Deserialize)]
+struct NewTagForm {
+    namespace: Option<i16>,
+    name: String,
+    /// 创建为指定标签的别名
+    alias_of: Option<i32>,
+}
+
+#[post("/item", data = "<data>")]
+async fn create_tag(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    data: Json<NewTagForm>,
+) -> Result<Json<InsertResponse<i32>>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let namespace = data.namespace.unwrap_or(TagNamespace::General as i16);
+    let name = data.name.trim();
+
+    if name.is_empty() || !is_valid_namespace(namespace) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let alias_of = match data.alias_of {
+        Some(alias_of) => Some(
+            canonical_tag(&mut conn, alias_of)
+                .await
+                .map_err(|err| match err {
+                    diesel::result::Error::NotFound => Status::UnprocessableEntity,
+                    err => tag_error_to_status(err),
+                })?
+                .id,
+        ),
+        None => None,
+    };
+
+    let new_tag_id = insert_into(schema::tags::table)
+        .values((
+            schema::tags::namespace.eq(namespace),
+            schema::tags::name.eq(name),
+            schema::tags::alias_of.eq(alias_of),
+        ))
+        .returning(schema::tags::id)
+        .get_result::<i32>(&mut conn)
+        .await
+        .map_err(tag_error_to_status)?;
+
+    info!("Create tag: {}", new_tag_id);
+
+    Ok(Json(InsertResponse { id: new_tag_id }))
+}
+
+#[derive(Deserialize)]
+struct UpdateTagForm {
+    namespace: Option<i16>,
+    name: Option<String>,
+}
+
+/// 重命名标签，同时改写小说和图片中的标签
+#[put("/item/<id>", data = "<data>")]
+async fn update_tag(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<UpdateTagForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let tag = schema::tags::table
+        .find(id)
+        .first::<Tag>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let namespace = data.namespace.unwrap_or(tag.namespace);
+    let name = data
+        .name
+        .as_deref()
+        .map(|v| v.trim())
+        .unwrap_or(&tag.name)
+        .to_owned();
+
+    if name.is_empty() || !is_valid_namespace(namespace) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            let new_key = update(schema::tags::table)
+                .filter(schema::tags::id.eq(id))
+                .set((
+                    schema::tags::namespace.eq(namespace),
+                    schema::tags::name.eq(name),
+                ))
+                .returning(schema::tags::key)
+                .get_result::<String>(conn)
+                .await?;
+
+            if new_key != tag.key {
+                rewrite_tags(conn, &tag.key, Some(&new_key)).await?;
+                // 计数触发器按key匹配，改名后旧key已不存在，需要重新统计
+                recount_tag(conn, id).await?;
+            }
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(tag_error_to_status)?;
+
+    info!("Update tag: {}", id);
+
+    Ok(Json(UpdateResponse { id }))
+}
+
+#[delete("/item/<id>")]
+async fn delete_tag(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+) -> Result<Json<DeleteResponse<i32>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let tag = schema::tags::table
+        .find(id)
+        .first::<Tag>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            rewrite_tags(conn, &tag.key, None).await?;
+
+            delete(schema::tags::table.filter(schema::tags::id.eq(id)))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|_| Status::InternalServerError)?;
+
+    info!("Delete tag: {}", id);
+
+    Ok(Json(DeleteResponse { id }))
+}
+
+#[derive(Deserialize)]
+struct MergeTagForm {
+    /// 合并到的目标标签
+    into: i32,
+}
+
+/// 将标签合并到另一个标签，原标签成为目标标签的别名
+#[post("/item/<id>/merge", data = "<data>")]
+async fn merge_tag(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<MergeTagForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let source = schema::tags::table
+        .find(id)
+        .first::<Tag>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    let target = canonical_tag(&mut conn, data.into)
+        .await
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => Status::UnprocessableEntity,
+            err => tag_error_to_status(err),
+        })?;
+
+    if target.id == source.id {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            rewrite_tags(conn, &source.key, Some(&target.key)).await?;
+
+            update(schema::tags::table)
+                .filter(schema::tags::alias_of.eq(source.id))
+                .set(schema::tags::alias_of.eq(target.id))
+                .execute(conn)
+                .await?;
+
+            // 隐含关系转移到目标标签
+            sql_query(
+                "INSERT INTO tag_implications (tag_id, implied_tag_id) \
+                 SELECT $2, implied_tag_id FROM tag_implications \
+                 WHERE tag_id = $1 AND implied_tag_id <> $2 \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind::<Integer, _>(source.id)
+            .bind::<Integer, _>(target.id)
+            .execute(conn)
+            .await?;
+
+            sql_query(
+                "INSERT INTO tag_implications (tag_id, implied_tag_id) \
+                 SELECT tag_id, $2 FROM tag_implications \
+                 WHERE implied_tag_id = $1 AND tag_id <> $2 \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind::<Integer, _>(source.id)
+            .bind::<Integer, _>(target.id)
+            .execute(conn)
+            .await?;
+
+            delete(
+                schema::tag_implications::table.filter(
+                    schema::tag_implications::tag_id
+                        .eq(source.id)
+                        .or(schema::tag_implications::implied_tag_id.eq(source.id)),
+                ),
+            )
+            .execute(conn)
+            .await?;
+
+            update(schema::tags::table)
+                .filter(schema::tags::id.eq(source.id))
+                .set(schema::tags::alias_of.eq(target.id))
+                .execute(conn)
+                .await?;
+
+            apply_implications(conn, &target).await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(tag_error_to_status)?;
+
+    info!("Merge tag {} into {}", id, data.into);
+
+    Ok(Json(UpdateResponse { id }))
+}
+
+#[derive(Deserialize)]
+struct ImplicationsForm {
+    implied_tag_ids: Vec<i32>,
+}
+
+/// 替换标签的隐含标签，并为已有的小说和图片补充
+#[put("/item/<id>/implications", data = "<data>")]
+async fn update_implications(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+    data: Json<ImplicationsForm>,
+) -> Result<Json<UpdateResponse<i32>>, Status> {
+    require_admin(auth)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let tag = schema::tags::table
+        .find(id)
+        .first::<Tag>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    // 别名不参与隐含关系
+    if tag.alias_of.is_some() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let mut implied_tag_ids = Vec::new();
+    for implied_tag_id in &data.implied_tag_ids {
+        let implied = canonical_tag(&mut conn, *implied_tag_id)
+            .await
+            .map_err(|err| match err {
+                diesel::result::Error::NotFound => Status::UnprocessableEntity,
+                err => tag_error_to_status(err),
+            })?;
+
+        if !implied_tag_ids.contains(&implied.id) {
+            implied_tag_ids.push(implied.id);
+        }
+    }
+
+    conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        async move {
+            delete(
+                schema::tag_implications::table.filter(schema::tag_implications::tag_id.eq(tag.id)),
+            )
+            .execute(conn)
+            .await?;
+
+            if !implied_tag_ids.is_empty() {
+                insert_into(schema::tag_implications::table)
+                    .values(
+                        implied_tag_ids
+                            .iter()
+                            .map(|implied_tag_id| {
+                                (
+                                    schema::tag_implications::tag_id.eq(tag.id),
+                                    schema::tag_implications::implied_tag_id.eq(implied_tag_id),
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .execute(conn)
+                    .await?;
+            }
+
+            apply_implications(conn, &tag).await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(tag_error_to_status)?;
+
+    info!("Update implications of tag: {}", id);
+
+    Ok(Json(UpdateResponse { id }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        list_tags,
+        get_tag,
+        create_tag,
+        update_tag,
+        delete_tag,
+        merge_tag,
+        update_implications
+    ]
+}
@@ -0,0 +1,31 @@
+use crate::{
+    db,
+    models::*,
+    schema,
+    utils::{result_error_to_status, ApiTokenClaims},
+};
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use rocket::{get, http::Status, serde::json::Json, Route, State};
+
+#[get("/item/<id>")]
+async fn get_task(
+    db: &State<db::Pool>,
+    auth: Option<ApiTokenClaims>,
+    id: i32,
+) -> Result<Json<Task>, Status> {
+    auth.ok_or(Status::Forbidden)?;
+    let mut conn = db.get().await.map_err(|_| Status::InternalServerError)?;
+
+    let task = schema::tasks::table
+        .find(id)
+        .first::<Task>(&mut conn)
+        .await
+        .map_err(result_error_to_status)?;
+
+    Ok(Json(task))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![get_task]
+}
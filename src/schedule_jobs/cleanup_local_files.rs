@@ -0,0 +1,293 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use crate::{markdown::EMBED_SCHEME, models::LocalFile, schema, utils::TransactionError, BUCKET};
+use anyhow::Context;
+use aws_sdk_s3::{operation::copy_object::CopyObjectError, types::MetadataDirective};
+use chrono::Utc;
+use diesel::{
+    dsl::{exists, not, AsExprOf, Concat},
+    sql_types::Text,
+    BoolExpressionMethods, ExpressionMethods, IntoSql, NullableExpressionMethods, QueryDsl,
+    SelectableHelper, TextExpressionMethods,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use log::{error, info};
+use rocket::http::{ContentType, RawStr};
+use std::{collections::HashMap, env};
+
+const QUARANTINE_PREFIX: &str = "quarantine/";
+
+#[derive(Clone, Debug)]
+pub struct CleanupConfig {
+    /// 未被引用的文件至少存在多久才会被清理
+    pub min_age: chrono::Duration,
+    /// 只记录将要清理的文件，不做任何修改
+    pub dry_run: bool,
+    /// 每次运行最多处理的文件数量
+    pub batch_size: i64,
+    /// 隔离区中的文件保留的天数
+    pub quarantine_days: i64,
+}
+
+impl CleanupConfig {
+    pub fn from_env() -> Self {
+        fn parse_env<T: std::str::FromStr>(key: &str, default: T) -> T {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            min_age: chrono::Duration::hours(parse_env("CLEANUP_MIN_AGE_HOURS", 24)),
+            dry_run: parse_env("CLEANUP_DRY_RUN", false),
+            batch_size: parse_env("CLEANUP_BATCH_SIZE", 500),
+            quarantine_days: parse_env("CLEANUP_QUARANTINE_DAYS", 7),
+        }
+    }
+}
+
+type EmbedPattern =
+    Concat<Concat<AsExprOf<String, Text>, schema::local_files::id>, AsExprOf<&'static str, Text>>;
+
+/// 匹配嵌入了当前文件的Markdown内容
+fn embed_pattern() -> EmbedPattern {
+    format!("%{}", EMBED_SCHEME)
+        .into_sql::<Text>()
+        .concat(schema::local_files::id)
+        .concat("%".into_sql::<Text>())
+}
+
+/// 隔离文件的元数据中保留被删除的local_files记录，恢复时据此重新写入
+fn quarantine_metadata(local_file: &LocalFile) -> HashMap<String, String> {
+    let mut metadata = HashMap::from([
+        ("local-file-id".to_owned(), local_file.id.to_owned()),
+        ("original-path".to_owned(), local_file.path.to_owned()),
+        ("created-at".to_owned(), local_file.created_at.to_rfc3339()),
+    ]);
+
+    // 元数据只能包含ASCII字符
+    if let Some(file_name) = &local_file.file_name {
+        metadata.insert(
+            "file-name".to_owned(),
+            RawStr::new(file_name).percent_encode().to_string(),
+        );
+    }
+
+    metadata
+}
+
+/// 将未被引用的文件移入隔离区，而不是直接删除
+async fn quarantine_unreferenced_files(
+    conn: &mut diesel_async::AsyncPgConnection,
+    s3_client: &aws_sdk_s3::Client,
+    config: &CleanupConfig,
+) -> Result<JobReport, diesel::result::Error> {
+    let created_before = Utc::now() - config.min_age;
+
+    let unreferenced_objects = schema::local_files::table
+        .left_join(schema::image_items_local_files::table)
+        .filter(schema::image_items_local_files::id.is_null())
+        .filter(not(exists(schema::novels::table.filter(
+            schema::novels::cover_local_file_id.eq(schema::local_files::id.nullable()),
+        ))))
+        // 作者的头像和横幅
+        .filter(not(exists(
+            schema::authors::table.filter(
+                schema::authors::avatar_local_file_id
+                    .eq(schema::local_files::id.nullable())
+                    .or(schema::authors::banner_local_file_id
+                        .eq(schema::local_files::id.nullable())),
+            ),
+        )))
+        // 被Markdown内容嵌入的文件
+        .filter(not(exists(schema::novels::table.filter(
+            schema::novels::description.like(embed_pattern().nullable()),
+        ))))
+        .filter(not(exists(
+            schema::novel_chapters::table
+                .filter(schema::novel_chapters::content.like(embed_pattern())),
+        )))
+        .filter(not(exists(schema::series::table.filter(
+            schema::series::description.like(embed_pattern().nullable()),
+        ))))
+        .filter(schema::local_files::created_at.lt(created_before))
+        .order(schema::local_files::created_at.asc())
+        .limit(config.batch_size)
+        .select(LocalFile::as_select())
+        .load::<LocalFile>(conn)
+        .await?;
+
+    info!(
+        "Found {} unreferenced objects older than {}",
+        unreferenced_objects.len(),
+        created_before.to_rfc3339()
+    );
+
+    let mut report = JobReport {
+        processed: unreferenced_objects.len() as i64,
+        affected: 0,
+    };
+
+    for unreferenced_object in unreferenced_objects {
+        let quarantine_key = format!("{}{}", QUARANTINE_PREFIX, unreferenced_object.path);
+
+        if config.dry_run {
+            info!(
+                "[dry-run] Would quarantine object: {} ({} -> {})",
+                unreferenced_object.id, unreferenced_object.path, quarantine_key
+            );
+            continue;
+        }
+
+        let result = conn
+            .transaction::<(), TransactionError<CopyObjectError>, _>(|conn| {
+                let unreferenced_object = unreferenced_object.to_owned();
+                let quarantine_key = quarantine_key.to_owned();
+                async move {
+                    diesel::delete(schema::local_files::table)
+                        .filter(schema::local_files::id.eq(&unreferenced_object.id))
+                        .execute(conn)
+                        .await
+                        .map_err(TransactionError::ResultError)?;
+
+                    s3_client
+                        .copy_object()
+                        .bucket(BUCKET)
+                        .copy_source(format!("{}/{}", BUCKET, unreferenced_object.path))
+                        .key(&quarantine_key)
+                        .metadata_directive(MetadataDirective::Replace)
+                        .set_content_type(
+                            unreferenced_object
+                                .path
+                                .rsplit_once('.')
+                                .and_then(|(_, ext)| ContentType::from_extension(ext))
+                                .map(|v| v.to_string()),
+                        )
+                        .set_metadata(Some(quarantine_metadata(&unreferenced_object)))
+                        .send()
+                        .await
+                        .map_err(TransactionError::SdkError)?;
+
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await;
+
+        if let Err(err) = result {
+            error!(
+                "Failed to quarantine object {}: {:?}",
+                unreferenced_object.id, err
+            );
+            continue;
+        }
+
+        // 隔离区中已有副本，原文件删除失败只会留下孤立对象
+        if let Err(err) = s3_client
+            .delete_object()
+            .bucket(BUCKET)
+            .key(&unreferenced_object.path)
+            .send()
+            .await
+        {
+            error!(
+                "Failed to delete quarantined source object {}: {:?}",
+                unreferenced_object.path, err
+            );
+        }
+
+        info!(
+            "Object quarantined: {} ({})",
+            unreferenced_object.id, quarantine_key
+        );
+        report.affected += 1;
+    }
+
+    Ok(report)
+}
+
+/// 永久删除隔离区中超过保留期限的文件
+async fn purge_quarantine(
+    s3_client: &aws_sdk_s3::Client,
+    config: &CleanupConfig,
+) -> anyhow::Result<JobReport> {
+    let expired_before = Utc::now() - chrono::Duration::days(config.quarantine_days);
+
+    let mut continuation_token: Option<String> = None;
+    let mut report = JobReport::default();
+
+    loop {
+        let resp = s3_client
+            .list_objects_v2()
+            .bucket(BUCKET)
+            .prefix(QUARANTINE_PREFIX)
+            .set_continuation_token(continuation_token.to_owned())
+            .send()
+            .await
+            .context("failed to list quarantined objects")?;
+
+        for object in resp.contents() {
+            if report.processed >= config.batch_size {
+                break;
+            }
+
+            let (Some(key), Some(last_modified)) = (object.key(), object.last_modified()) else {
+                continue;
+            };
+
+            if last_modified.secs() >= expired_before.timestamp() {
+                continue;
+            }
+
+            report.processed += 1;
+
+            if config.dry_run {
+                info!("[dry-run] Would purge quarantined object: {}", key);
+                continue;
+            }
+
+            match s3_client.delete_object().bucket(BUCKET).key(key).send().await {
+                Ok(_) => {
+                    info!("Quarantined object purged: {}", key);
+                    report.affected += 1;
+                }
+                Err(err) => error!("Failed to purge quarantined object {}: {:?}", key, err),
+            }
+        }
+
+        continuation_token = resp.next_continuation_token().map(|v| v.to_owned());
+        if continuation_token.is_none() || report.processed >= config.batch_size {
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let config = CleanupConfig::from_env();
+    info!("Cleanup config: {:?}", config);
+
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let quarantined = quarantine_unreferenced_files(&mut conn, &ctx.s3_client, &config)
+        .await
+        .context("failed to load unreferenced objects")?;
+
+    let purged = purge_quarantine(&ctx.s3_client, &config).await?;
+
+    Ok(JobReport {
+        processed: quarantined.processed + purged.processed,
+        affected: quarantined.affected + purged.affected,
+    })
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "cleanup_local_files",
+        description: "Quarantine unreferenced local files and purge expired quarantined objects",
+        schedule: JobSchedule::DailyAt("00:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
+
@@ -0,0 +1,131 @@
+use crate::{db, models::JobLease, schema, utils::instance_id};
+use diesel::{
+    dsl::now,
+    sql_query,
+    sql_types::{BigInt, Text},
+    ExpressionMethods, OptionalExtension, QueryDsl,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use log::error;
+use std::{env, time::Duration};
+
+/// 多实例部署时，通过job_leases表保证同一任务只在一个实例上运行
+#[derive(Clone, Debug)]
+pub struct LeaseConfig {
+    /// 当前实例的标识，会显示在任务状态中
+    pub holder: String,
+    /// 租约有效期，持有者崩溃后其他实例需等待该时长才能接管
+    pub ttl: Duration,
+}
+
+impl LeaseConfig {
+    pub fn from_env() -> Self {
+        let ttl_secs = env::var("JOB_LEASE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(600);
+
+        Self {
+            holder: instance_id(),
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+}
+
+/// 尝试获取任务租约，租约已过期或已由当前实例持有时才能成功
+pub async fn try_acquire(
+    conn: &mut AsyncPgConnection,
+    job_name: &str,
+    config: &LeaseConfig,
+) -> Result<bool, diesel::result::Error> {
+    let affected = sql_query(
+        "INSERT INTO job_leases (job_name, holder, acquired_at, expires_at) \
+         VALUES ($1, $2, now(), now() + make_interval(secs => $3)) \
+         ON CONFLICT (job_name) DO UPDATE \
+         SET holder = EXCLUDED.holder, acquired_at = EXCLUDED.acquired_at, expires_at = EXCLUDED.expires_at \
+         WHERE job_leases.expires_at < now() OR job_leases.holder = EXCLUDED.holder",
+    )
+    .bind::<Text, _>(job_name)
+    .bind::<Text, _>(&config.holder)
+    .bind::<BigInt, _>(config.ttl.as_secs() as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(affected > 0)
+}
+
+/// 延长当前实例持有的租约
+pub async fn renew(
+    conn: &mut AsyncPgConnection,
+    job_name: &str,
+    config: &LeaseConfig,
+) -> Result<bool, diesel::result::Error> {
+    let affected = sql_query(
+        "UPDATE job_leases SET expires_at = now() + make_interval(secs => $3) \
+         WHERE job_name = $1 AND holder = $2",
+    )
+    .bind::<Text, _>(job_name)
+    .bind::<Text, _>(&config.holder)
+    .bind::<BigInt, _>(config.ttl.as_secs() as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(affected > 0)
+}
+
+pub async fn release(
+    conn: &mut AsyncPgConnection,
+    job_name: &str,
+    config: &LeaseConfig,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(schema::job_leases::table)
+        .filter(schema::job_leases::job_name.eq(job_name))
+        .filter(schema::job_leases::holder.eq(&config.holder))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// 查询任务当前有效的租约
+pub async fn current(
+    conn: &mut AsyncPgConnection,
+    job_name: &str,
+) -> Result<Option<JobLease>, diesel::result::Error> {
+    schema::job_leases::table
+        .filter(schema::job_leases::job_name.eq(job_name))
+        .filter(schema::job_leases::expires_at.gt(now))
+        .first::<JobLease>(conn)
+        .await
+        .optional()
+}
+
+/// 在任务运行期间定期续约，直到返回的句柄被中止
+pub fn spawn_heartbeat(
+    pool: db::Pool,
+    job_name: &'static str,
+    config: LeaseConfig,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.ttl / 3);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            let mut conn = match pool.get().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    error!("Failed to renew lease of job {}: {:?}", job_name, err);
+                    continue;
+                }
+            };
+
+            match renew(&mut conn, job_name, &config).await {
+                Ok(true) => {}
+                Ok(false) => error!("Lease of job {} was lost", job_name),
+                Err(err) => error!("Failed to renew lease of job {}: {:?}", job_name, err),
+            }
+        }
+    })
+}
@@ -0,0 +1,331 @@
+use crate::{
+    db,
+    misc::enums::{JobRunStatus, JobTrigger},
+    schema,
+};
+use chrono::Utc;
+use clokwerk::{AsyncScheduler, Job, TimeUnits};
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use futures::FutureExt;
+use log::{error, info};
+use std::{
+    any::Any,
+    collections::HashSet,
+    fmt,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+mod cleanup_local_files;
+pub mod lease;
+mod prune_rendered_markdown;
+mod purge_orphaned_objects;
+mod rebuild_author_links;
+mod refresh_author_stats;
+mod verify_image_items_grouped;
+
+pub use lease::LeaseConfig;
+
+/// 任务运行时可使用的共享资源
+#[derive(Clone)]
+pub struct JobContext {
+    pub db: db::Pool,
+    pub s3_client: aws_sdk_s3::Client,
+}
+
+/// 任务运行结束后记录的统计数量
+#[derive(Debug, Default)]
+pub struct JobReport {
+    pub processed: i64,
+    pub affected: i64,
+}
+
+pub type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<JobReport>> + Send>>;
+
+#[derive(Clone, Copy, Debug)]
+pub enum JobSchedule {
+    DailyAt(&'static str),
+}
+
+impl JobSchedule {
+    /// 两次计划运行之间的间隔
+    pub fn period(&self) -> chrono::Duration {
+        match self {
+            JobSchedule::DailyAt(_) => chrono::Duration::days(1),
+        }
+    }
+}
+
+impl fmt::Display for JobSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobSchedule::DailyAt(time) => write!(f, "daily at {}", time),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct JobDefinition {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub schedule: JobSchedule,
+    pub handler: fn(JobContext) -> JobFuture,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    NotFound,
+    AlreadyRunning,
+    LeasedElsewhere,
+    AlreadyScheduled,
+    PoolError,
+    ResultError(diesel::result::Error),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::NotFound => write!(f, "job not found"),
+            JobError::AlreadyRunning => write!(f, "job is already running"),
+            JobError::LeasedElsewhere => write!(f, "job is running on another instance"),
+            JobError::AlreadyScheduled => write!(f, "job already ran for this schedule"),
+            JobError::PoolError => write!(f, "failed to get connection"),
+            JobError::ResultError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for JobError {
+    fn from(value: diesel::result::Error) -> Self {
+        Self::ResultError(value)
+    }
+}
+
+#[derive(Clone)]
+pub struct JobRegistry {
+    jobs: Arc<Vec<JobDefinition>>,
+    running: Arc<Mutex<HashSet<&'static str>>>,
+    lease_config: LeaseConfig,
+    ctx: JobContext,
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// 任务结束(包括panic)时将其从运行列表中移除
+struct RunningGuard {
+    name: &'static str,
+    running: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(self.name);
+    }
+}
+
+impl JobRegistry {
+    pub fn new(ctx: JobContext, lease_config: LeaseConfig) -> Self {
+        Self {
+            jobs: Arc::new(vec![
+                cleanup_local_files::definition(),
+                verify_image_items_grouped::definition(),
+                refresh_author_stats::definition(),
+                prune_rendered_markdown::definition(),
+                rebuild_author_links::definition(),
+                purge_orphaned_objects::definition(),
+            ]),
+            running: Arc::new(Mutex::new(HashSet::new())),
+            lease_config,
+            ctx,
+        }
+    }
+
+    pub fn jobs(&self) -> &[JobDefinition] {
+        &self.jobs
+    }
+
+    pub fn find(&self, name: &str) -> Option<&JobDefinition> {
+        self.jobs.iter().find(|job| job.name == name)
+    }
+
+    pub fn is_running(&self, name: &str) -> bool {
+        self.running.lock().unwrap().contains(name)
+    }
+
+    /// 在持有租约的情况下写入运行记录，计划触发时跳过其他实例已经执行过的同一次计划
+    async fn record_start(
+        &self,
+        conn: &mut AsyncPgConnection,
+        job: &JobDefinition,
+        trigger: JobTrigger,
+    ) -> Result<i32, JobError> {
+        if let JobTrigger::Schedule = trigger {
+            let recent_runs = schema::job_runs::table
+                .filter(schema::job_runs::job_name.eq(job.name))
+                .filter(schema::job_runs::trigger.eq(JobTrigger::Schedule as i16))
+                .filter(schema::job_runs::started_at.gt(Utc::now() - job.schedule.period() / 2))
+                .count()
+                .get_result::<i64>(conn)
+                .await?;
+
+            if recent_runs > 0 {
+                return Err(JobError::AlreadyScheduled);
+            }
+        }
+
+        let run_id = insert_into(schema::job_runs::table)
+            .values((
+                schema::job_runs::job_name.eq(job.name),
+                schema::job_runs::trigger.eq(trigger as i16),
+                schema::job_runs::status.eq(JobRunStatus::Running as i16),
+            ))
+            .returning(schema::job_runs::id)
+            .get_result::<i32>(conn)
+            .await?;
+
+        Ok(run_id)
+    }
+
+    /// 记录一次新的运行并在后台执行，返回运行记录的id
+    pub async fn start(&self, name: &str, trigger: JobTrigger) -> Result<i32, JobError> {
+        let job = *self.find(name).ok_or(JobError::NotFound)?;
+
+        if !self.running.lock().unwrap().insert(job.name) {
+            return Err(JobError::AlreadyRunning);
+        }
+        let guard = RunningGuard {
+            name: job.name,
+            running: self.running.clone(),
+        };
+
+        let mut conn = self.ctx.db.get().await.map_err(|_| JobError::PoolError)?;
+
+        if !lease::try_acquire(&mut conn, job.name, &self.lease_config).await? {
+            return Err(JobError::LeasedElsewhere);
+        }
+
+        let run_id = match self.record_start(&mut conn, &job, trigger).await {
+            Ok(run_id) => run_id,
+            Err(err) => {
+                if let Err(err) = lease::release(&mut conn, job.name, &self.lease_config).await {
+                    error!("Failed to release lease of job {}: {:?}", job.name, err);
+                }
+                return Err(err);
+            }
+        };
+
+        drop(conn);
+
+        let ctx = self.ctx.clone();
+        let lease_config = self.lease_config.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+
+            info!(
+                "Job started: {} (run {}) on {}",
+                job.name, run_id, lease_config.holder
+            );
+
+            let heartbeat =
+                lease::spawn_heartbeat(ctx.db.clone(), job.name, lease_config.to_owned());
+            // panic时同样记录为失败，否则运行记录会一直停留在Running
+            let result = AssertUnwindSafe((job.handler)(ctx.clone()))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|panic| {
+                    Err(anyhow::anyhow!("job panicked: {}", panic_message(&*panic)))
+                });
+            heartbeat.abort();
+
+            let (status, report, err) = match result {
+                Ok(report) => {
+                    info!(
+                        "Job finished: {} (run {}), processed {}, affected {}",
+                        job.name, run_id, report.processed, report.affected
+                    );
+                    (JobRunStatus::Succeeded, report, None)
+                }
+                Err(err) => {
+                    error!("Job failed: {} (run {}): {:#}", job.name, run_id, err);
+                    (
+                        JobRunStatus::Failed,
+                        JobReport::default(),
+                        Some(format!("{:#}", err)),
+                    )
+                }
+            };
+
+            let Ok(mut conn) = ctx.db.get().await else {
+                error!("Failed to record result of job run {}", run_id);
+                return;
+            };
+
+            if let Err(err) = update(schema::job_runs::table)
+                .filter(schema::job_runs::id.eq(run_id))
+                .set((
+                    schema::job_runs::status.eq(status as i16),
+                    schema::job_runs::processed_count.eq(report.processed),
+                    schema::job_runs::affected_count.eq(report.affected),
+                    schema::job_runs::error.eq(err),
+                    schema::job_runs::finished_at.eq(Utc::now()),
+                ))
+                .execute(&mut conn)
+                .await
+            {
+                error!("Failed to record result of job run {}: {:?}", run_id, err);
+            }
+
+            if let Err(err) = lease::release(&mut conn, job.name, &lease_config).await {
+                error!("Failed to release lease of job {}: {:?}", job.name, err);
+            }
+        });
+
+        Ok(run_id)
+    }
+}
+
+pub async fn init(registry: JobRegistry) {
+    let mut scheduler = AsyncScheduler::new();
+
+    for job in registry.jobs() {
+        let name = job.name;
+        let registry = registry.clone();
+        let run = move || {
+            let registry = registry.clone();
+            async move {
+                match registry.start(name, JobTrigger::Schedule).await {
+                    Ok(_) => {}
+                    Err(err @ (JobError::LeasedElsewhere | JobError::AlreadyScheduled)) => {
+                        info!("Scheduled job {} skipped: {}", name, err)
+                    }
+                    Err(err) => error!("Failed to start scheduled job {}: {}", name, err),
+                }
+            }
+        };
+
+        match job.schedule {
+            JobSchedule::DailyAt(time) => scheduler.every(1.day()).at(time).run(run),
+        };
+    }
+
+    tokio::spawn(async move {
+        loop {
+            scheduler.run_pending().await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+
+    info!("Schedule jobs started");
+}
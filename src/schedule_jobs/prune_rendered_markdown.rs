@@ -0,0 +1,43 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use crate::schema;
+use anyhow::Context;
+use chrono::Utc;
+use diesel::{delete, ExpressionMethods};
+use diesel_async::RunQueryDsl;
+use log::info;
+
+/// 缓存保留的天数，仍在使用的内容被删除后会在下次请求时重新渲染
+const MAX_AGE_DAYS: i64 = 30;
+
+/// 删除过期的Markdown渲染缓存，内容修改后旧的缓存不会再被读取
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let created_before = Utc::now() - chrono::Duration::days(MAX_AGE_DAYS);
+
+    let deleted = delete(schema::rendered_markdown::table)
+        .filter(schema::rendered_markdown::created_at.lt(created_before))
+        .execute(&mut conn)
+        .await
+        .context("failed to prune rendered_markdown")?;
+
+    info!(
+        "Pruned {} rendered markdown entries older than {}",
+        deleted,
+        created_before.to_rfc3339()
+    );
+
+    Ok(JobReport {
+        processed: deleted as i64,
+        affected: deleted as i64,
+    })
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "prune_rendered_markdown",
+        description: "Delete rendered markdown cache entries older than 30 days",
+        schedule: JobSchedule::DailyAt("03:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
@@ -0,0 +1,69 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use crate::{schema, BUCKET};
+use anyhow::Context;
+use diesel::{delete, dsl::exists, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use log::{error, info};
+
+/// 每次运行最多处理的对象数量
+const BATCH_SIZE: i64 = 500;
+
+/// 删除不再有site_storage记录引用的S3对象
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let keys = schema::orphaned_objects::table
+        .select(schema::orphaned_objects::key)
+        .order(schema::orphaned_objects::created_at.asc())
+        .limit(BATCH_SIZE)
+        .load::<String>(&mut conn)
+        .await
+        .context("failed to load orphaned objects")?;
+
+    let mut report = JobReport::default();
+
+    for key in keys {
+        report.processed += 1;
+
+        // 相同内容重新写入后key会再次被引用，此时只移除待删除记录
+        let referenced = diesel::select(exists(
+            schema::site_storage::table.filter(schema::site_storage::key.eq(&key)),
+        ))
+        .get_result::<bool>(&mut conn)
+        .await
+        .context("failed to check site_storage references")?;
+
+        if !referenced {
+            if let Err(err) = ctx
+                .s3_client
+                .delete_object()
+                .bucket(BUCKET)
+                .key(&key)
+                .send()
+                .await
+            {
+                error!("Failed to delete orphaned object {}: {:?}", key, err);
+                continue;
+            }
+            info!("Orphaned object deleted: {}", key);
+            report.affected += 1;
+        }
+
+        delete(schema::orphaned_objects::table)
+            .filter(schema::orphaned_objects::key.eq(&key))
+            .execute(&mut conn)
+            .await
+            .context("failed to delete orphaned object record")?;
+    }
+
+    Ok(report)
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "purge_orphaned_objects",
+        description: "Delete S3 objects no longer referenced by site storage records",
+        schedule: JobSchedule::DailyAt("04:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
@@ -0,0 +1,37 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use crate::author_links;
+use anyhow::Context;
+use log::{info, warn};
+
+/// 根据作者的urls重建author_links，也用于补全新增链接识别规则前已有的作者
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let (authors, conflicts) = author_links::rebuild_author_links(&mut conn)
+        .await
+        .context("failed to rebuild author links")?;
+
+    if !conflicts.is_empty() {
+        warn!(
+            "{} authors have links used by another author: {:?}",
+            conflicts.len(),
+            conflicts
+        );
+    }
+
+    info!("Author links rebuilt: {}", authors);
+
+    Ok(JobReport {
+        processed: authors,
+        affected: authors - conflicts.len() as i64,
+    })
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "rebuild_author_links",
+        description: "Rebuild author_links from the urls of every author",
+        schedule: JobSchedule::DailyAt("05:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
@@ -0,0 +1,53 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use anyhow::Context;
+use diesel::{sql_query, sql_types::BigInt, QueryableByName};
+use diesel_async::RunQueryDsl;
+use log::info;
+
+#[derive(QueryableByName, Debug)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// 作者统计接口使用的物化视图，由同一个任务依次刷新
+const VIEWS: &[&str] = &[
+    "author_monthly_stats",
+    "author_appearance",
+    "author_tag_stats",
+];
+
+/// 刷新作者统计接口使用的物化视图，CONCURRENTLY保证刷新期间仍可读取
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let mut report = JobReport::default();
+
+    for view in VIEWS {
+        sql_query(format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}", view))
+            .execute(&mut conn)
+            .await
+            .with_context(|| format!("failed to refresh {}", view))?;
+
+        let rows = sql_query(format!("SELECT count(*) AS count FROM {}", view))
+            .get_result::<RowCount>(&mut conn)
+            .await
+            .with_context(|| format!("failed to count {}", view))?;
+
+        info!("{} refreshed ({} rows)", view, rows.count);
+
+        report.processed += rows.count;
+        report.affected += rows.count;
+    }
+
+    Ok(report)
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "refresh_author_stats",
+        description: "Refresh the materialized views used by author statistics",
+        schedule: JobSchedule::DailyAt("04:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
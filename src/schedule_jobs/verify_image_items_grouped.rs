@@ -0,0 +1,56 @@
+use super::{JobContext, JobDefinition, JobFuture, JobReport, JobSchedule};
+use anyhow::Context;
+use diesel::{sql_query, sql_types::BigInt, QueryableByName};
+use diesel_async::RunQueryDsl;
+use log::{info, warn};
+
+#[derive(QueryableByName, Debug)]
+struct GroupingDrift {
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+    /// 在image_items_grouped中没有对应记录的image_items
+    #[diesel(sql_type = BigInt)]
+    missing: i64,
+    /// 分组日期与image_items不一致的记录
+    #[diesel(sql_type = BigInt)]
+    mismatched: i64,
+}
+
+/// image_items_grouped由数据库触发器维护，这里只检查并报告不一致的记录，不做修改
+async fn run(ctx: JobContext) -> anyhow::Result<JobReport> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let drift = sql_query(
+        "SELECT count(*) AS total, \
+             count(*) FILTER (WHERE g.image_item_id IS NULL) AS missing, \
+             count(*) FILTER (WHERE g.date <> i.date) AS mismatched \
+         FROM image_items i \
+         LEFT JOIN image_items_grouped g ON g.image_item_id = i.id",
+    )
+    .get_result::<GroupingDrift>(&mut conn)
+    .await
+    .context("failed to verify image item grouping")?;
+
+    if drift.missing > 0 || drift.mismatched > 0 {
+        warn!(
+            "image_items_grouped drift detected: {} missing, {} mismatched out of {} items",
+            drift.missing, drift.mismatched, drift.total
+        );
+    } else {
+        info!("image_items_grouped is consistent ({} items)", drift.total);
+    }
+
+    Ok(JobReport {
+        processed: drift.total,
+        affected: drift.missing + drift.mismatched,
+    })
+}
+
+pub fn definition() -> JobDefinition {
+    JobDefinition {
+        name: "verify_image_items_grouped",
+        description: "Report image_items_grouped rows that drifted from image_items",
+        schedule: JobSchedule::DailyAt("00:00"),
+        handler: |ctx| -> JobFuture { Box::pin(run(ctx)) },
+    }
+}
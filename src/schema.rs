@@ -1,10 +1,32 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    author_aliases (id) {
+        id -> Int4,
+        author_id -> Int4,
+        name -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    author_links (id) {
+        id -> Int4,
+        author_id -> Int4,
+        platform -> Int2,
+        handle -> Text,
+        url -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     authors (id) {
         id -> Int4,
         name -> Text,
         urls -> Nullable<Array<Nullable<Text>>>,
+        avatar_local_file_id -> Nullable<Text>,
+        banner_local_file_id -> Nullable<Text>,
     }
 }
 
@@ -15,6 +37,7 @@ diesel::table! {
         date -> Date,
         nsfw -> Bool,
         author_id -> Nullable<Int4>,
+        tags -> Array<Nullable<Text>>,
     }
 }
 
@@ -33,12 +56,54 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    job_leases (job_name) {
+        job_name -> Text,
+        holder -> Text,
+        acquired_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    job_runs (id) {
+        id -> Int4,
+        job_name -> Text,
+        trigger -> Int2,
+        status -> Int2,
+        processed_count -> Int8,
+        affected_count -> Int8,
+        error -> Nullable<Text>,
+        started_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     local_files (id) {
         id -> Text,
         file_name -> Nullable<Text>,
         path -> Text,
         created_at -> Timestamptz,
+        source_url -> Nullable<Text>,
+        source_final_url -> Nullable<Text>,
+        fetched_at -> Nullable<Timestamptz>,
+        source_headers -> Nullable<Jsonb>,
+        importer -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    novel_chapters (id) {
+        id -> Int4,
+        novel_id -> Int4,
+        position -> Int4,
+        title -> Text,
+        content -> Text,
+        word_count -> Int4,
+        published_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -55,6 +120,41 @@ diesel::table! {
         object_id -> Nullable<Int4>,
         created_by -> Nullable<Int4>,
         created_at -> Timestamptz,
+        cover_local_file_id -> Nullable<Text>,
+        author_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    orphaned_objects (key) {
+        key -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    rendered_markdown (hash) {
+        hash -> Text,
+        html -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    series (id) {
+        id -> Int4,
+        title -> Text,
+        description -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    series_novels (id) {
+        id -> Int4,
+        series_id -> Int4,
+        novel_id -> Int4,
+        position -> Int4,
     }
 }
 
@@ -69,21 +169,79 @@ diesel::table! {
         mime_type -> Text,
         created_by -> Nullable<Int4>,
         created_at -> Timestamptz,
+        metadata -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    tag_implications (tag_id, implied_tag_id) {
+        tag_id -> Int4,
+        implied_tag_id -> Int4,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Int4,
+        namespace -> Int2,
+        name -> Text,
+        key -> Text,
+        alias_of -> Nullable<Int4>,
+        novel_count -> Int4,
+        created_at -> Timestamptz,
+        image_count -> Int4,
+    }
+}
+
+diesel::table! {
+    tasks (id) {
+        id -> Int4,
+        kind -> Int2,
+        status -> Int2,
+        payload -> Jsonb,
+        result -> Nullable<Jsonb>,
+        error -> Nullable<Text>,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_after -> Timestamptz,
+        locked_by -> Nullable<Text>,
+        locked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
     }
 }
 
+diesel::joinable!(author_aliases -> authors (author_id));
+diesel::joinable!(author_links -> authors (author_id));
 diesel::joinable!(image_items -> authors (author_id));
 diesel::joinable!(image_items_grouped -> image_items (image_item_id));
 diesel::joinable!(image_items_local_files -> image_items (image_item_id));
 diesel::joinable!(image_items_local_files -> local_files (local_file_id));
+diesel::joinable!(novel_chapters -> novels (novel_id));
+diesel::joinable!(novels -> authors (author_id));
+diesel::joinable!(novels -> local_files (cover_local_file_id));
 diesel::joinable!(novels -> site_storage (object_id));
+diesel::joinable!(series_novels -> novels (novel_id));
+diesel::joinable!(series_novels -> series (series_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    author_aliases,
+    author_links,
     authors,
     image_items,
     image_items_grouped,
     image_items_local_files,
+    job_leases,
+    job_runs,
     local_files,
+    novel_chapters,
     novels,
+    orphaned_objects,
+    rendered_markdown,
+    series,
+    series_novels,
     site_storage,
+    tag_implications,
+    tags,
+    tasks,
 );
@@ -0,0 +1,583 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use diesel::{
+    dsl::not,
+    expression::{is_aggregate, AppearsOnTable, BoxableExpression, ValidGrouping},
+    pg::Pg,
+    query_builder::{AstPass, QueryFragment, QueryId},
+    sql_types::Bool,
+    BoolExpressionMethods, Expression, QueryResult, SelectableExpression, Table,
+};
+use rocket::http::Status;
+use serde::Serialize;
+
+use crate::utils::response::{ApiError, ErrorDetail};
+
+/// 标签查询语法树
+///
+/// 语法：空格分隔的条件同时满足，`|`表示任一满足，`-`表示排除，括号用于分组，
+/// `字段:值`形式的条件按接口支持的字段筛选，其余均视为标签
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagQuery {
+    Tag(String),
+    Field {
+        name: String,
+        value: String,
+        position: usize,
+    },
+    Not(Box<TagQuery>),
+    And(Vec<TagQuery>),
+    Or(Vec<TagQuery>),
+}
+
+/// 查询语法错误，position为出错位置的字符偏移
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TagQueryError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl TagQueryError {
+    pub fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for TagQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for TagQueryError {}
+
+impl From<TagQueryError> for ApiError {
+    fn from(value: TagQueryError) -> Self {
+        ApiError::Detailed(
+            Status::UnprocessableEntity,
+            ErrorDetail {
+                message: value.message,
+                position: Some(value.position),
+            },
+        )
+    }
+}
+
+/// 查询字符串的最大字符数
+const MAX_QUERY_LENGTH: usize = 1024;
+
+/// 括号和`-`的最大嵌套层数，解析和生成SQL都是递归进行的
+const MAX_NESTING_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    Pipe,
+    Minus,
+    /// 原始文本中第一段未加引号的部分用于识别字段
+    Term {
+        value: String,
+        bare_prefix: String,
+    },
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, TagQueryError> {
+    let chars = input.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let token = match c {
+            '(' => Some(Token::LeftParen),
+            ')' => Some(Token::RightParen),
+            '|' => Some(Token::Pipe),
+            // 只有位于条件开头的`-`表示排除
+            '-' => Some(Token::Minus),
+            _ => None,
+        };
+
+        if let Some(token) = token {
+            tokens.push((token, index));
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        let mut value = String::new();
+        let mut bare_prefix = String::new();
+        let mut quoted = false;
+
+        while index < chars.len() {
+            let c = chars[index];
+
+            if c.is_whitespace() || matches!(c, '(' | ')' | '|') {
+                break;
+            }
+
+            if c == '"' {
+                let quote_start = index;
+                index += 1;
+
+                loop {
+                    match chars.get(index) {
+                        Some('"') => break,
+                        Some(c) => value.push(*c),
+                        None => return Err(TagQueryError::new("unterminated quote", quote_start)),
+                    }
+                    index += 1;
+                }
+
+                quoted = true;
+                index += 1;
+                continue;
+            }
+
+            if !quoted {
+                bare_prefix.push(c);
+            }
+            value.push(c);
+            index += 1;
+        }
+
+        tokens.push((Token::Term { value, bare_prefix }, start));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+    end: usize,
+    depth: usize,
+    fields: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(v, _)| v)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map(|(_, v)| *v)
+            .unwrap_or(self.end)
+    }
+
+    /// 进入一层嵌套，超过最大层数时返回错误
+    fn enter(&mut self, position: usize) -> Result<(), TagQueryError> {
+        self.depth += 1;
+
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(TagQueryError::new("query is nested too deeply", position));
+        }
+
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<TagQuery, TagQueryError> {
+        let mut items = vec![self.parse_and()?];
+
+        while self.peek() == Some(&Token::Pipe) {
+            self.index += 1;
+            items.push(self.parse_and()?);
+        }
+
+        Ok(if items.len() == 1 {
+            items.remove(0)
+        } else {
+            TagQuery::Or(items)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<TagQuery, TagQueryError> {
+        let mut items = Vec::new();
+
+        while !matches!(
+            self.peek(),
+            None | Some(Token::Pipe) | Some(Token::RightParen)
+        ) {
+            items.push(self.parse_unary()?);
+        }
+
+        match items.len() {
+            0 if self.peek() == Some(&Token::RightParen) => {
+                Err(TagQueryError::new("unexpected ')'", self.position()))
+            }
+            0 => Err(TagQueryError::new("expected a term", self.position())),
+            1 => Ok(items.remove(0)),
+            _ => Ok(TagQuery::And(items)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<TagQuery, TagQueryError> {
+        if self.peek() == Some(&Token::Minus) {
+            self.enter(self.position())?;
+            self.index += 1;
+
+            let inner = match self.peek() {
+                None | Some(Token::Pipe) | Some(Token::RightParen) => Err(TagQueryError::new(
+                    "expected a term after '-'",
+                    self.position(),
+                )),
+                _ => Ok(TagQuery::Not(Box::new(self.parse_unary()?))),
+            };
+
+            self.depth -= 1;
+            return inner;
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<TagQuery, TagQueryError> {
+        let position = self.position();
+
+        match self.tokens.get(self.index).map(|(v, _)| v.to_owned()) {
+            Some(Token::LeftParen) => {
+                self.enter(position)?;
+                self.index += 1;
+                let inner = self.parse_or()?;
+
+                if self.peek() != Some(&Token::RightParen) {
+                    return Err(TagQueryError::new("unclosed '('", position));
+                }
+
+                self.index += 1;
+                self.depth -= 1;
+                Ok(inner)
+            }
+            Some(Token::Term { value, bare_prefix }) => {
+                self.index += 1;
+
+                if let Some((name, _)) = bare_prefix.split_once(':') {
+                    if self.fields.contains(&name) {
+                        return Ok(TagQuery::Field {
+                            name: name.to_owned(),
+                            value: value[name.len() + 1..].to_owned(),
+                            position,
+                        });
+                    }
+                }
+
+                if value.is_empty() {
+                    return Err(TagQueryError::new("empty tag", position));
+                }
+
+                Ok(TagQuery::Tag(value))
+            }
+            Some(Token::RightParen) => Err(TagQueryError::new("unexpected ')'", position)),
+            _ => Err(TagQueryError::new("expected a term", position)),
+        }
+    }
+}
+
+/// 解析查询字符串，fields为接口支持的字段名，空查询返回None
+pub fn parse_tag_query(input: &str, fields: &[&str]) -> Result<Option<TagQuery>, TagQueryError> {
+    if input.chars().count() > MAX_QUERY_LENGTH {
+        return Err(TagQueryError::new(
+            format!("query is longer than {} characters", MAX_QUERY_LENGTH),
+            MAX_QUERY_LENGTH,
+        ));
+    }
+
+    let tokens = tokenize(input)?;
+
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: input.chars().count(),
+        depth: 0,
+        fields,
+    };
+
+    let query = parser.parse_or()?;
+
+    if parser.index < parser.tokens.len() {
+        return Err(TagQueryError::new("unexpected ')'", parser.position()));
+    }
+
+    Ok(Some(query))
+}
+
+impl TagQuery {
+    /// 查询中出现的全部标签，用于解析别名
+    pub fn tags(&self) -> Vec<String> {
+        match self {
+            TagQuery::Tag(tag) => vec![tag.to_owned()],
+            TagQuery::Field { .. } => Vec::new(),
+            TagQuery::Not(inner) => inner.tags(),
+            TagQuery::And(items) | TagQuery::Or(items) => {
+                items.iter().flat_map(|v| v.tags()).collect()
+            }
+        }
+    }
+}
+
+pub type BoxedFilter<T> = Box<dyn BoxableExpression<T, Pg, SqlType = Bool> + Sync>;
+
+/// 生成单个标签的条件
+pub type TagFilterFn<T> = dyn Fn(String) -> BoxedFilter<T>;
+
+/// 生成`字段:值`的条件，值不合法时返回错误信息
+pub type FieldFilterFn<T> = dyn Fn(&str, &str) -> Result<BoxedFilter<T>, String>;
+
+/// 标签查询生成的筛选条件
+///
+/// 条件只引用表T中的列，因此可以用于任何包含表T的查询，包括join之后的查询，
+/// 复制时共享同一个条件，列表和计数查询不需要分别生成
+pub struct TagQueryFilter<T> {
+    inner: Arc<dyn BoxableExpression<T, Pg, SqlType = Bool> + Sync>,
+}
+
+impl<T> Clone for TagQueryFilter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Expression for TagQueryFilter<T> {
+    type SqlType = Bool;
+}
+
+impl<T> QueryFragment<Pg> for TagQueryFilter<T> {
+    fn walk_ast<'b>(&'b self, pass: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        self.inner.walk_ast(pass)
+    }
+}
+
+impl<T> QueryId for TagQueryFilter<T> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<T> ValidGrouping<()> for TagQueryFilter<T> {
+    type IsAggregate = is_aggregate::No;
+}
+
+impl<T, QS> AppearsOnTable<QS> for TagQueryFilter<T>
+where
+    T: Table,
+    T::PrimaryKey: AppearsOnTable<QS>,
+{
+}
+
+impl<T, QS> SelectableExpression<QS> for TagQueryFilter<T>
+where
+    T: Table,
+    T::PrimaryKey: SelectableExpression<QS>,
+{
+}
+
+/// 将查询转换为diesel筛选条件
+///
+/// resolved为标签到规范标签的映射，tag和field分别生成单个标签和字段的条件，
+/// field返回的错误信息会附带该字段的位置
+pub fn build_tag_query_filter<T>(
+    query: &TagQuery,
+    resolved: &HashMap<String, String>,
+    tag: &TagFilterFn<T>,
+    field: &FieldFilterFn<T>,
+) -> Result<TagQueryFilter<T>, TagQueryError>
+where
+    T: Table + 'static,
+    T::PrimaryKey: SelectableExpression<T>,
+{
+    let build = |query| build_tag_query_filter(query, resolved, tag, field);
+
+    let inner: Arc<dyn BoxableExpression<T, Pg, SqlType = Bool> + Sync> = match query {
+        TagQuery::Tag(value) => Arc::from(tag(resolved.get(value).unwrap_or(value).to_owned())),
+        TagQuery::Field {
+            name,
+            value,
+            position,
+        } => {
+            Arc::from(field(name, value).map_err(|message| TagQueryError::new(message, *position))?)
+        }
+        TagQuery::Not(inner) => Arc::new(not(build(inner)?)),
+        TagQuery::And(items) => {
+            let mut items = items.iter();
+            let mut filter = build(items.next().expect("non-empty"))?;
+            for item in items {
+                filter = TagQueryFilter {
+                    inner: Arc::new(filter.and(build(item)?)),
+                };
+            }
+            filter.inner
+        }
+        TagQuery::Or(items) => {
+            let mut items = items.iter();
+            let mut filter = build(items.next().expect("non-empty"))?;
+            for item in items {
+                filter = TagQueryFilter {
+                    inner: Arc::new(filter.or(build(item)?)),
+                };
+            }
+            filter.inner
+        }
+    };
+
+    Ok(TagQueryFilter { inner })
+}
+
+/// 解析`true`/`false`形式的字段值
+pub fn parse_bool_field(name: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!("{} expects true or false", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &[&str] = &["author", "nsfw"];
+
+    fn parse(input: &str) -> TagQuery {
+        parse_tag_query(input, FIELDS).unwrap().unwrap()
+    }
+
+    fn tag(value: &str) -> TagQuery {
+        TagQuery::Tag(value.to_owned())
+    }
+
+    fn error(input: &str) -> TagQueryError {
+        parse_tag_query(input, FIELDS).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        // 空格的优先级高于`|`
+        assert_eq!(
+            parse("a b | c"),
+            TagQuery::Or(vec![TagQuery::And(vec![tag("a"), tag("b")]), tag("c")])
+        );
+        assert_eq!(
+            parse("a | b c"),
+            TagQuery::Or(vec![tag("a"), TagQuery::And(vec![tag("b"), tag("c")])])
+        );
+        // `-`只作用于紧随其后的条件
+        assert_eq!(
+            parse("-a b"),
+            TagQuery::And(vec![TagQuery::Not(Box::new(tag("a"))), tag("b")])
+        );
+        assert_eq!(
+            parse("a (b | c)"),
+            TagQuery::And(vec![tag("a"), TagQuery::Or(vec![tag("b"), tag("c")])])
+        );
+        assert_eq!(
+            parse("-(a | b)"),
+            TagQuery::Not(Box::new(TagQuery::Or(vec![tag("a"), tag("b")])))
+        );
+        // 条件中间的`-`是标签的一部分
+        assert_eq!(parse("sci-fi"), tag("sci-fi"));
+        assert_eq!(parse_tag_query("   ", FIELDS), Ok(None));
+    }
+
+    #[test]
+    fn quoted_terms() {
+        assert_eq!(parse("\"a b\""), tag("a b"));
+        assert_eq!(
+            parse("author:\"x y\" c"),
+            TagQuery::And(vec![
+                TagQuery::Field {
+                    name: "author".to_owned(),
+                    value: "x y".to_owned(),
+                    position: 0,
+                },
+                tag("c"),
+            ])
+        );
+        // 字段名在引号内时视为标签
+        assert_eq!(parse("\"author:x\""), tag("author:x"));
+        assert_eq!(parse("\"a | (b)\""), tag("a | (b)"));
+    }
+
+    #[test]
+    fn unknown_field_is_tag() {
+        assert_eq!(parse("foo:bar"), tag("foo:bar"));
+        assert_eq!(
+            parse("nsfw:true"),
+            TagQuery::Field {
+                name: "nsfw".to_owned(),
+                value: "true".to_owned(),
+                position: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        let cases = [
+            ("a \"bc", "unterminated quote", 2),
+            ("a (b c", "unclosed '('", 2),
+            ("a )", "unexpected ')'", 2),
+            (")", "unexpected ')'", 0),
+            ("a -", "expected a term after '-'", 3),
+            ("|", "expected a term", 0),
+            ("a |", "expected a term", 3),
+            ("\"\"", "empty tag", 0),
+        ];
+
+        for (input, message, position) in cases {
+            assert_eq!(
+                error(input),
+                TagQueryError::new(message, position),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = format!("{}a{}", "(".repeat(32), ")".repeat(32));
+        assert_eq!(parse(&nested), tag("a"));
+
+        let too_deep = format!("{}a", "(".repeat(MAX_NESTING_DEPTH + 1));
+        assert_eq!(
+            error(&too_deep),
+            TagQueryError::new("query is nested too deeply", MAX_NESTING_DEPTH)
+        );
+
+        let negated = format!("{}a", "-".repeat(MAX_NESTING_DEPTH + 1));
+        assert_eq!(
+            error(&negated),
+            TagQueryError::new("query is nested too deeply", MAX_NESTING_DEPTH)
+        );
+
+        // 不会因为递归过深而溢出栈
+        assert!(parse_tag_query(&"(".repeat(100_000), FIELDS).is_err());
+        assert!(parse_tag_query(&"-".repeat(100_000), FIELDS).is_err());
+    }
+
+    #[test]
+    fn length_limit() {
+        assert!(parse_tag_query(&"a ".repeat(MAX_QUERY_LENGTH / 2), FIELDS).is_ok());
+        assert_eq!(
+            error(&"a ".repeat(MAX_QUERY_LENGTH)).position,
+            MAX_QUERY_LENGTH
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use super::{NonRetryableError, PendingUpload, TaskContext};
+use crate::{
+    image_pipeline::{
+        import_web_images, prepare_pending_image, store_pending_images, ImageProcessError,
+    },
+    utils::response::InsertResponse,
+    BUCKET,
+};
+use anyhow::{anyhow, Context};
+use log::error;
+
+/// 删除暂存的上传文件
+pub async fn delete_uploads(s3_client: &aws_sdk_s3::Client, uploads: &[PendingUpload]) {
+    for upload in uploads {
+        if let Err(err) = s3_client
+            .delete_object()
+            .bucket(BUCKET)
+            .key(&upload.key)
+            .send()
+            .await
+        {
+            error!("Failed to delete pending upload {}: {:?}", upload.key, err);
+        }
+    }
+}
+
+/// 处理暂存的上传文件，成功后删除暂存对象
+pub async fn process_uploads(
+    ctx: &TaskContext,
+    uploads: Vec<PendingUpload>,
+) -> anyhow::Result<serde_json::Value> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let mut pending_images = Vec::with_capacity(uploads.len());
+    for upload in &uploads {
+        let object = ctx
+            .s3_client
+            .get_object()
+            .bucket(BUCKET)
+            .key(&upload.key)
+            .send()
+            .await
+            .with_context(|| format!("failed to read pending upload {}", upload.key))?;
+
+        let data = object
+            .body
+            .collect()
+            .await
+            .with_context(|| format!("failed to read pending upload {}", upload.key))?
+            .into_bytes()
+            .to_vec();
+
+        let name = upload.file_name.as_deref().unwrap_or(&upload.key);
+
+        pending_images.push(prepare_pending_image(&mut conn, data).await.map_err(
+            |err| match err {
+                // 无法解码的文件重试也不会成功
+                ImageProcessError::Decode(_) => anyhow::Error::new(NonRetryableError(format!(
+                    "failed to process {}: {}",
+                    name, err
+                ))),
+                err => anyhow::Error::new(err).context(format!("failed to process {}", name)),
+            },
+        )?);
+    }
+
+    let ids = store_pending_images(&mut conn, &ctx.s3_client, pending_images)
+        .await
+        .map_err(|err| anyhow!("failed to store images: {:?}", err))?;
+
+    delete_uploads(&ctx.s3_client, &uploads).await;
+
+    Ok(serde_json::to_value(InsertResponse { id: ids })?)
+}
+
+/// 下载网络图片，结果与item_from_web_multi接口相同
+pub async fn process_web_images(
+    ctx: &TaskContext,
+    urls: Vec<String>,
+) -> anyhow::Result<serde_json::Value> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let results = import_web_images(&mut conn, &ctx.s3_client, &ctx.web_fetcher, urls)
+        .await
+        .map_err(|err| anyhow!("failed to store images: {:?}", err))?;
+
+    Ok(serde_json::to_value(results)?)
+}
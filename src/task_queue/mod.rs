@@ -0,0 +1,332 @@
+use crate::{
+    db,
+    misc::enums::{TaskKind, TaskStatus},
+    models::Task,
+    schema,
+    utils::instance_id,
+    web_fetch::WebFetcher,
+};
+use anyhow::Context;
+use chrono::Utc;
+use diesel::{
+    insert_into, sql_query,
+    sql_types::{BigInt, Text},
+    update, ExpressionMethods, OptionalExtension,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{env, sync::Arc, time::Duration};
+use tokio::sync::Notify;
+
+mod image;
+mod novel;
+
+pub use image::delete_uploads;
+
+/// 重试也不会成功的错误，任务会直接标记为失败
+#[derive(Debug)]
+pub struct NonRetryableError(pub String);
+
+impl std::fmt::Display for NonRetryableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NonRetryableError {}
+
+fn is_retryable(err: &anyhow::Error) -> bool {
+    !err.chain().any(|v| v.is::<NonRetryableError>())
+}
+
+/// 后台任务运行时可使用的共享资源
+#[derive(Clone)]
+pub struct TaskContext {
+    pub db: db::Pool,
+    pub s3_client: aws_sdk_s3::Client,
+    pub web_fetcher: WebFetcher,
+}
+
+/// 暂存在存储桶中等待处理的上传文件
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingUpload {
+    pub key: String,
+    pub file_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPayload {
+    ImageUpload { uploads: Vec<PendingUpload> },
+    ImageFromWeb { urls: Vec<String> },
+    NovelCover { novel_id: i32 },
+}
+
+impl TaskPayload {
+    pub fn kind(&self) -> TaskKind {
+        match self {
+            TaskPayload::ImageUpload { .. } => TaskKind::ImageUpload,
+            TaskPayload::ImageFromWeb { .. } => TaskKind::ImageFromWeb,
+            TaskPayload::NovelCover { .. } => TaskKind::NovelCover,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskQueueConfig {
+    /// 当前实例启动的worker数量
+    pub workers: usize,
+    /// 队列为空时的轮询间隔
+    pub poll_interval: Duration,
+    /// 超过该时长仍处于运行状态的任务视为worker已崩溃，可被重新领取
+    pub lock_timeout: Duration,
+    pub max_attempts: i32,
+}
+
+impl TaskQueueConfig {
+    pub fn from_env() -> Self {
+        fn parse_env<T: std::str::FromStr>(key: &str, default: T) -> T {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            workers: parse_env("TASK_WORKERS", 2),
+            poll_interval: Duration::from_secs(parse_env("TASK_POLL_INTERVAL_SECS", 5)),
+            lock_timeout: Duration::from_secs(parse_env("TASK_LOCK_TIMEOUT_SECS", 900)),
+            max_attempts: parse_env("TASK_MAX_ATTEMPTS", 3),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskQueue {
+    ctx: TaskContext,
+    config: TaskQueueConfig,
+    worker_id: String,
+    notify: Arc<Notify>,
+}
+
+impl TaskQueue {
+    pub fn new(ctx: TaskContext, config: TaskQueueConfig) -> Self {
+        Self {
+            ctx,
+            config,
+            worker_id: instance_id(),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// 写入新任务并唤醒一个空闲的worker，返回任务id
+    pub async fn enqueue(
+        &self,
+        conn: &mut AsyncPgConnection,
+        payload: TaskPayload,
+    ) -> Result<i32, diesel::result::Error> {
+        let payload_value = serde_json::to_value(&payload)
+            .map_err(|err| diesel::result::Error::SerializationError(Box::new(err)))?;
+
+        let task_id = insert_into(schema::tasks::table)
+            .values((
+                schema::tasks::kind.eq(payload.kind() as i16),
+                schema::tasks::status.eq(TaskStatus::Pending as i16),
+                schema::tasks::payload.eq(payload_value),
+                schema::tasks::max_attempts.eq(self.config.max_attempts),
+            ))
+            .returning(schema::tasks::id)
+            .get_result::<i32>(conn)
+            .await?;
+
+        self.notify.notify_one();
+
+        Ok(task_id)
+    }
+
+    pub fn start_workers(&self) {
+        for index in 0..self.config.workers {
+            let queue = self.clone();
+            tokio::spawn(async move { queue.work(index).await });
+        }
+
+        info!("Task workers started: {}", self.config.workers);
+    }
+
+    async fn work(&self, index: usize) {
+        loop {
+            match self.claim().await {
+                Ok(Some(task)) => self.execute(task).await,
+                Ok(None) => {
+                    if let Err(err) = self.fail_abandoned().await {
+                        error!(
+                            "Task worker {} failed to sweep abandoned tasks: {:#}",
+                            index, err
+                        );
+                    }
+
+                    tokio::select! {
+                        _ = self.notify.notified() => {}
+                        _ = tokio::time::sleep(self.config.poll_interval) => {}
+                    }
+                }
+                Err(err) => {
+                    error!("Task worker {} failed to claim task: {:#}", index, err);
+                    tokio::time::sleep(self.config.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// 领取一个待处理的任务，SKIP LOCKED保证多个worker不会领取同一个任务
+    async fn claim(&self) -> anyhow::Result<Option<Task>> {
+        let mut conn = self.ctx.db.get().await.context("failed to get connection")?;
+
+        let task = sql_query(
+            "UPDATE tasks SET status = $1, attempts = attempts + 1, locked_by = $2, locked_at = now() \
+             WHERE id = ( \
+                 SELECT id FROM tasks \
+                 WHERE (status = $3 AND run_after <= now()) \
+                    OR (status = $1 AND locked_at < now() - make_interval(secs => $4) \
+                        AND attempts < max_attempts) \
+                 ORDER BY id \
+                 FOR UPDATE SKIP LOCKED \
+                 LIMIT 1 \
+             ) \
+             RETURNING *",
+        )
+        .bind::<diesel::sql_types::SmallInt, _>(TaskStatus::Running as i16)
+        .bind::<Text, _>(&self.worker_id)
+        .bind::<diesel::sql_types::SmallInt, _>(TaskStatus::Pending as i16)
+        .bind::<BigInt, _>(self.config.lock_timeout.as_secs() as i64)
+        .get_result::<Task>(&mut conn)
+        .await
+        .optional()?;
+
+        Ok(task)
+    }
+
+    /// 将锁已超时且没有剩余重试次数的任务标记为失败，
+    /// 通常是执行时worker崩溃(例如内存不足)，再次执行也会失败
+    async fn fail_abandoned(&self) -> anyhow::Result<()> {
+        let mut conn = self.ctx.db.get().await.context("failed to get connection")?;
+
+        let tasks = sql_query(
+            "UPDATE tasks SET status = $1, error = $2, finished_at = now(), \
+                 locked_by = NULL, locked_at = NULL \
+             WHERE status = $3 AND locked_at < now() - make_interval(secs => $4) \
+                AND attempts >= max_attempts \
+             RETURNING *",
+        )
+        .bind::<diesel::sql_types::SmallInt, _>(TaskStatus::Failed as i16)
+        .bind::<Text, _>("worker stopped while running the task")
+        .bind::<diesel::sql_types::SmallInt, _>(TaskStatus::Running as i16)
+        .bind::<BigInt, _>(self.config.lock_timeout.as_secs() as i64)
+        .load::<Task>(&mut conn)
+        .await?;
+
+        drop(conn);
+
+        for task in tasks {
+            error!(
+                "Task abandoned: {} (attempt {}), worker stopped while running it",
+                task.id, task.attempts
+            );
+
+            if let Ok(payload) = serde_json::from_value::<TaskPayload>(task.payload) {
+                self.discard(&payload).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self, task: Task) {
+        info!("Task started: {} (attempt {})", task.id, task.attempts);
+
+        let payload = serde_json::from_value::<TaskPayload>(task.payload.to_owned());
+
+        let result = match &payload {
+            Ok(payload) => self.dispatch(payload.to_owned()).await,
+            Err(err) => Err(anyhow::anyhow!("invalid task payload: {}", err)),
+        };
+
+        let Ok(mut conn) = self.ctx.db.get().await else {
+            error!("Failed to record result of task {}", task.id);
+            return;
+        };
+
+        let update_result = match result {
+            Ok(value) => {
+                info!("Task succeeded: {}", task.id);
+                update(schema::tasks::table)
+                    .filter(schema::tasks::id.eq(task.id))
+                    .set((
+                        schema::tasks::status.eq(TaskStatus::Succeeded as i16),
+                        schema::tasks::result.eq(Some(value)),
+                        schema::tasks::error.eq(None::<String>),
+                        schema::tasks::finished_at.eq(Some(Utc::now())),
+                    ))
+                    .execute(&mut conn)
+                    .await
+            }
+            Err(err) if task.attempts < task.max_attempts && is_retryable(&err) => {
+                // 指数退避后重试
+                let delay = chrono::Duration::seconds(10 * 2_i64.pow(task.attempts as u32));
+                error!(
+                    "Task failed: {} (attempt {}), retrying in {}s: {:#}",
+                    task.id,
+                    task.attempts,
+                    delay.num_seconds(),
+                    err
+                );
+                update(schema::tasks::table)
+                    .filter(schema::tasks::id.eq(task.id))
+                    .set((
+                        schema::tasks::status.eq(TaskStatus::Pending as i16),
+                        schema::tasks::error.eq(Some(format!("{:#}", err))),
+                        schema::tasks::run_after.eq(Utc::now() + delay),
+                        schema::tasks::locked_by.eq(None::<String>),
+                        schema::tasks::locked_at.eq(None::<chrono::DateTime<Utc>>),
+                    ))
+                    .execute(&mut conn)
+                    .await
+            }
+            Err(err) => {
+                error!("Task failed: {} (attempt {}): {:#}", task.id, task.attempts, err);
+                if let Ok(payload) = &payload {
+                    self.discard(payload).await;
+                }
+                update(schema::tasks::table)
+                    .filter(schema::tasks::id.eq(task.id))
+                    .set((
+                        schema::tasks::status.eq(TaskStatus::Failed as i16),
+                        schema::tasks::error.eq(Some(format!("{:#}", err))),
+                        schema::tasks::finished_at.eq(Some(Utc::now())),
+                    ))
+                    .execute(&mut conn)
+                    .await
+            }
+        };
+
+        if let Err(err) = update_result {
+            error!("Failed to record result of task {}: {:?}", task.id, err);
+        }
+    }
+
+    /// 任务最终失败后清理其暂存的数据
+    async fn discard(&self, payload: &TaskPayload) {
+        if let TaskPayload::ImageUpload { uploads } = payload {
+            delete_uploads(&self.ctx.s3_client, uploads).await;
+        }
+    }
+
+    async fn dispatch(&self, payload: TaskPayload) -> anyhow::Result<serde_json::Value> {
+        match payload {
+            TaskPayload::ImageUpload { uploads } => image::process_uploads(&self.ctx, uploads).await,
+            TaskPayload::ImageFromWeb { urls } => image::process_web_images(&self.ctx, urls).await,
+            TaskPayload::NovelCover { novel_id } => novel::generate_cover(&self.ctx, novel_id).await,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use super::TaskContext;
+use crate::{
+    image_pipeline::{prepare_pending_image, store_pending_images},
+    models::{Novel, SiteStorage},
+    novel_format::NovelFormat,
+    novel_metadata::extract_novel_cover,
+    schema,
+    utils::response::InsertResponse,
+    BUCKET,
+};
+use anyhow::{anyhow, Context};
+use diesel::{update, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use log::info;
+
+/// 从小说关联的文件中提取封面并写入cover_local_file_id，没有可用封面时返回null
+pub async fn generate_cover(ctx: &TaskContext, novel_id: i32) -> anyhow::Result<serde_json::Value> {
+    let mut conn = ctx.db.get().await.context("failed to get connection")?;
+
+    let novel = schema::novels::table
+        .find(novel_id)
+        .first::<Novel>(&mut conn)
+        .await
+        .with_context(|| format!("failed to load novel {}", novel_id))?;
+
+    let Some(object_id) = novel.object_id else {
+        return Ok(serde_json::Value::Null);
+    };
+
+    let object = schema::site_storage::table
+        .find(object_id)
+        .first::<SiteStorage>(&mut conn)
+        .await
+        .with_context(|| format!("failed to load object {}", object_id))?;
+
+    let Some(format) = NovelFormat::from_mime_type(&object.mime_type) else {
+        return Ok(serde_json::Value::Null);
+    };
+
+    let data = ctx
+        .s3_client
+        .get_object()
+        .bucket(BUCKET)
+        .key(&object.key)
+        .send()
+        .await
+        .with_context(|| format!("failed to read object {}", object.key))?
+        .body
+        .collect()
+        .await
+        .with_context(|| format!("failed to read object {}", object.key))?
+        .into_bytes()
+        .to_vec();
+
+    let cover = tokio::task::spawn_blocking(move || extract_novel_cover(format, &data))
+        .await
+        .context("cover extraction was interrupted")??;
+
+    let Some(cover) = cover else {
+        return Ok(serde_json::Value::Null);
+    };
+
+    let pending_image = prepare_pending_image(&mut conn, cover)
+        .await
+        .context("failed to process cover")?;
+
+    let cover_id = store_pending_images(&mut conn, &ctx.s3_client, vec![pending_image])
+        .await
+        .map_err(|err| anyhow!("failed to store cover: {:?}", err))?
+        .remove(0);
+
+    // 任务执行期间关联的文件可能已被更换
+    update(schema::novels::table)
+        .filter(schema::novels::id.eq(novel_id))
+        .filter(schema::novels::object_id.eq(object_id))
+        .set(schema::novels::cover_local_file_id.eq(&cover_id))
+        .execute(&mut conn)
+        .await
+        .context("failed to update novel cover")?;
+
+    info!("Novel cover generated: {} ({})", novel_id, cover_id);
+
+    Ok(serde_json::to_value(InsertResponse { id: cover_id })?)
+}
@@ -120,6 +120,25 @@ impl<'r> FromRequest<'r> for ApiTokenClaims {
     }
 }
 
+/// 要求请求带有管理员的token
+pub fn require_admin(auth: Option<ApiTokenClaims>) -> Result<(), Status> {
+    if auth.ok_or(Status::Forbidden)?.admin {
+        Ok(())
+    } else {
+        Err(Status::Forbidden)
+    }
+}
+
+/// 当前实例的标识，未设置INSTANCE_ID时使用主机名和进程id
+pub fn instance_id() -> String {
+    std::env::var("INSTANCE_ID").unwrap_or_else(|_| {
+        let hostname = std::fs::read_to_string("/etc/hostname")
+            .map(|v| v.trim().to_owned())
+            .unwrap_or_else(|_| "unknown".to_owned());
+        format!("{}:{}", hostname, std::process::id())
+    })
+}
+
 pub struct ParsedOrderBy {
     pub column: String,
     pub direction: QueryOrderDirection,
@@ -148,6 +167,12 @@ pub fn parse_order_from_string(str: String) -> Vec<Option<ParsedOrderBy>> {
 }
 
 pub mod response {
+    use rocket::{
+        http::Status,
+        response::{self, Responder},
+        serde::json::Json,
+        Request,
+    };
     use serde::Serialize;
 
     #[derive(Serialize, Debug, Clone)]
@@ -181,6 +206,35 @@ pub mod response {
             self
         }
     }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct ErrorDetail {
+        pub message: String,
+        /// 出错位置，用于查询语法等输入错误
+        pub position: Option<usize>,
+    }
+
+    /// 需要返回错误信息时使用，其余情况与直接返回Status相同
+    #[derive(Debug)]
+    pub enum ApiError {
+        Status(Status),
+        Detailed(Status, ErrorDetail),
+    }
+
+    impl From<Status> for ApiError {
+        fn from(value: Status) -> Self {
+            ApiError::Status(value)
+        }
+    }
+
+    impl<'r> Responder<'r, 'static> for ApiError {
+        fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+            match self {
+                ApiError::Status(status) => status.respond_to(request),
+                ApiError::Detailed(status, detail) => (status, Json(detail)).respond_to(request),
+            }
+        }
+    }
 }
 
 pub mod datetime_format {
@@ -206,6 +260,35 @@ pub mod datetime_format {
     }
 }
 
+pub mod datetime_format_option {
+    use chrono::{DateTime, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => serializer.serialize_str(&date.to_rfc3339()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => Ok(Some(
+                DateTime::parse_from_rfc3339(s.as_str())
+                    .map_err(serde::de::Error::custom)?
+                    .into(),
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
 pub mod naive_date_format {
     use chrono::NaiveDate;
     use serde::{self, Deserialize, Deserializer, Serializer};
@@ -0,0 +1,402 @@
+use std::{
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect, Url,
+};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use rocket::http::Status;
+
+#[derive(Debug)]
+pub enum FetchError {
+    RequestError(reqwest_middleware::Error),
+    InvalidUrl,
+    /// 地址指向本机、内网或云服务的元数据地址
+    Blocked(String),
+    TooLarge,
+    NotImage,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::RequestError(err) => write!(f, "failed to fetch: {}", err),
+            FetchError::InvalidUrl => write!(f, "invalid url"),
+            FetchError::Blocked(host) => write!(f, "address is not allowed: {}", host),
+            FetchError::TooLarge => write!(f, "response is too large"),
+            FetchError::NotImage => write!(f, "response is not an image"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::RequestError(value.into())
+    }
+}
+
+impl From<reqwest_middleware::Error> for FetchError {
+    fn from(value: reqwest_middleware::Error) -> Self {
+        Self::RequestError(value)
+    }
+}
+
+pub fn fetch_error_to_status(err: FetchError) -> Status {
+    match err {
+        FetchError::NotImage | FetchError::Blocked(_) => Status::UnprocessableEntity,
+        FetchError::TooLarge => Status::PayloadTooLarge,
+        _ => Status::BadRequest,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WebFetchConfig {
+    /// 单个响应的最大字节数
+    pub max_bytes: usize,
+    /// 从连接开始到读取完响应的总时长
+    pub timeout: Duration,
+    pub max_redirects: usize,
+    /// 批量下载时同时进行的请求数
+    pub concurrency: usize,
+}
+
+impl WebFetchConfig {
+    pub fn from_env() -> Self {
+        fn parse_env<T: std::str::FromStr>(key: &str, default: T) -> T {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        }
+
+        Self {
+            max_bytes: parse_env("WEB_FETCH_MAX_BYTES", 20 * 1024 * 1024),
+            timeout: Duration::from_secs(parse_env("WEB_FETCH_TIMEOUT_SECS", 30)),
+            max_redirects: parse_env("WEB_FETCH_MAX_REDIRECTS", 5),
+            concurrency: parse_env("WEB_FETCH_CONCURRENCY", 4).max(1),
+        }
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        // 169.254.0.0/16，包括云服务的元数据地址169.254.169.254
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15
+        || (a == 198 && (18..20).contains(&b))
+        // 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(v4);
+    }
+
+    let segments = ip.segments();
+    let embedded_ipv4 = |high: u16, low: u16| {
+        let [a, b] = high.to_be_bytes();
+        let [c, d] = low.to_be_bytes();
+        Ipv4Addr::new(a, b, c, d)
+    };
+
+    // 64:ff9b::/96 NAT64
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        return is_public_ipv4(embedded_ipv4(segments[6], segments[7]));
+    }
+
+    // 2002::/16 6to4，IPv4地址位于第二、三段
+    if segments[0] == 0x2002 {
+        return is_public_ipv4(embedded_ipv4(segments[1], segments[2]));
+    }
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // ::/96 已废弃的IPv4兼容地址
+        || segments[..6] == [0, 0, 0, 0, 0, 0]
+        // 2001::/32 Teredo，无法可靠地判断实际连接的地址
+        || (segments[0] == 0x2001 && segments[1] == 0)
+        // 64:ff9b:1::/48 NAT64本地使用
+        || segments[..3] == [0x64, 0xff9b, 1]
+        // fc00::/7
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// 是否为可以从外部访问的地址
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+/// 只允许http和https，地址为IP时必须是公网地址，域名在解析时检查
+fn check_url(url: &Url) -> Result<(), FetchError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(FetchError::InvalidUrl);
+    }
+
+    let host = url.host_str().ok_or(FetchError::InvalidUrl)?;
+
+    // IPv6地址带有方括号
+    match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) if !is_public_ip(ip) => Err(FetchError::Blocked(ip.to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// 解析域名并去掉非公网地址，没有剩余地址时返回Blocked
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, FetchError> {
+    let addrs = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|_| FetchError::InvalidUrl)?
+        .filter(|addr| is_public_ip(addr.ip()))
+        .collect::<Vec<SocketAddr>>();
+
+    if addrs.is_empty() {
+        return Err(FetchError::Blocked(host.to_owned()));
+    }
+
+    Ok(addrs)
+}
+
+/// 每次建立连接(包括重定向)都会经过这里，因此解析结果在检查之后发生变化也无法绕过
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Addrs = Box::new(resolve_public(name.as_str()).await?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 解析或重定向时被阻止的请求转换为Blocked
+fn request_error(err: reqwest_middleware::Error) -> FetchError {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&err);
+
+    while let Some(err) = source {
+        if let Some(FetchError::Blocked(host)) = err.downcast_ref::<FetchError>() {
+            return FetchError::Blocked(host.to_owned());
+        }
+        source = err.source();
+    }
+
+    FetchError::RequestError(err)
+}
+
+/// 记录来源时保留的响应头
+const KEPT_HEADERS: &[&str] = &["content-type", "content-length", "last-modified", "etag"];
+
+pub struct FetchedResponse {
+    /// 重定向后的地址
+    pub final_url: String,
+    pub headers: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+/// 下载用户提供的地址，阻止访问内网地址并限制响应大小和时长
+#[derive(Clone)]
+pub struct WebFetcher {
+    client: ClientWithMiddleware,
+    config: WebFetchConfig,
+}
+
+impl WebFetcher {
+    pub fn new(config: WebFetchConfig) -> Self {
+        let max_redirects = config.max_redirects;
+
+        let redirect_policy = redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else if let Err(err) = check_url(attempt.url()) {
+                attempt.error(err)
+            } else {
+                attempt.follow()
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(redirect_policy)
+            // 代理会绕过DNS解析的检查
+            .no_proxy()
+            .timeout(config.timeout)
+            .build()
+            .expect("failed to build reqwest client");
+
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+
+        Self {
+            client: ClientBuilder::new(client)
+                .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+                .build(),
+            config,
+        }
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.config.concurrency
+    }
+
+    /// 下载地址的内容，超过max_bytes时立即中止
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        self.fetch_with_headers(url, &[]).await
+    }
+
+    /// 附带请求头下载，部分站点需要Referer等请求头
+    pub async fn fetch_with_headers(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+    ) -> Result<Vec<u8>, FetchError> {
+        Ok(self.fetch_response(url, headers).await?.data)
+    }
+
+    /// 下载并保留重定向后的地址和部分响应头
+    pub async fn fetch_response(
+        &self,
+        url: &str,
+        headers: &[(String, String)],
+    ) -> Result<FetchedResponse, FetchError> {
+        let parsed = Url::parse(url.trim()).map_err(|_| FetchError::InvalidUrl)?;
+        check_url(&parsed)?;
+
+        // 提前检查，避免被阻止的连接错误触发重试
+        if let Some(host) = parsed.domain() {
+            resolve_public(host).await?;
+        }
+
+        let mut request = self.client.get(parsed);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+
+        let mut resp = request
+            .send()
+            .await
+            .map_err(request_error)?
+            .error_for_status()?;
+
+        if resp
+            .content_length()
+            .is_some_and(|v| v > self.config.max_bytes as u64)
+        {
+            return Err(FetchError::TooLarge);
+        }
+
+        let final_url = resp.url().to_string();
+        let kept_headers = KEPT_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = resp.headers().get(*name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_owned()))
+            })
+            .collect();
+
+        let mut data = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            if data.len() + chunk.len() > self.config.max_bytes {
+                return Err(FetchError::TooLarge);
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchedResponse {
+            final_url,
+            headers: kept_headers,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv4() {
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.255",
+            "192.0.0.1",
+            "198.18.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+        ] {
+            assert!(!is_public(ip), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "1.1.1.1"] {
+            assert!(is_public(ip), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn ipv6() {
+        for ip in [
+            "::",
+            "::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b:1::808:808",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+            "2001:0:4136:e378::1",
+            "::127.0.0.1",
+            "::8.8.8.8",
+            "fd00::1",
+            "fe80::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(ip), "{}", ip);
+        }
+        for ip in [
+            "::ffff:8.8.8.8",
+            "64:ff9b::808:808",
+            "2002:808:808::1",
+            "2606:4700::1111",
+        ] {
+            assert!(is_public(ip), "{}", ip);
+        }
+    }
+}